@@ -0,0 +1,112 @@
+//! Choice sequences: the raw randomness consumed by generators.
+//!
+//! Every generated value is a function of the `u64`s drawn from the RNG,
+//! so a recorded sequence of those choices can be edited and replayed
+//! to produce a related (but still valid) set of values. Choices can
+//! also be read from a plain byte buffer, such as a fuzzer input.
+
+/// An RNG which replays a fixed sequence of choices, then draws
+/// any further choices from `fallback`. All choices used are recorded.
+#[derive(Clone)]
+pub(crate) struct ChoiceRng<R> {
+    choices: Vec<u64>,
+    position: usize,
+    fallback: R,
+}
+
+impl<R: rand::RngCore> ChoiceRng<R> {
+    pub(crate) fn new(choices: Vec<u64>, fallback: R) -> Self {
+        ChoiceRng {
+            choices,
+            position: 0,
+            fallback,
+        }
+    }
+
+    /// Draws a choice below `bound`, and records it as the reduced value, so
+    /// that smaller recorded choices always give smaller results.
+    pub(crate) fn next_below(&mut self, bound: u64) -> u64 {
+        let choice = rand::RngCore::next_u64(self) % bound;
+        self.choices[self.position - 1] = choice;
+        choice
+    }
+
+    /// The choices that have been used so far.
+    pub(crate) fn into_choices(mut self) -> Vec<u64> {
+        self.choices.truncate(self.position);
+        self.choices
+    }
+}
+
+impl<R: rand::RngCore> rand::RngCore for ChoiceRng<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if self.position == self.choices.len() {
+            self.choices.push(self.fallback.next_u64());
+        }
+
+        let choice = self.choices[self.position];
+        self.position += 1;
+        choice
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// An RNG which reads its choices from a byte buffer,
+/// such as the input provided by a fuzzer.
+/// Once the buffer is exhausted, all further choices are zero.
+///
+/// Since the values generated from a buffer depend only on the generators
+/// used, a buffer can be kept as a corpus file and replayed
+/// with [crate::Config::input].
+#[derive(Clone)]
+pub struct BufferRng<'a> {
+    buffer: &'a [u8],
+}
+
+impl<'a> BufferRng<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        BufferRng { buffer }
+    }
+}
+
+impl rand::RngCore for BufferRng<'_> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let len = dest.len().min(self.buffer.len());
+        let (head, tail) = self.buffer.split_at(len);
+        dest[..len].copy_from_slice(head);
+        dest[len..].fill(0);
+        self.buffer = tail;
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+//! Statistics about which kinds of values were tested,
+//! as reported by [crate::Maat::label] and friends.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    time::Duration,
+};
+
+use crate::{Failure, Observations, Stats};
+
+/// The labels seen across all iterations of a run.
+#[derive(Default)]
+pub(crate) struct Coverage {
+    /// The number of iterations with each label.
+    counts: BTreeMap<String, usize>,
+    /// The minimum percentage of iterations required for each label,
+    /// as given to [crate::Maat::cover].
+    required: BTreeMap<String, f64>,
+}
+
+impl Coverage {
+    pub(crate) fn add(&mut self, observations: &Observations) {
+        // an iteration counts once, however many times it is labelled
+        let labels: BTreeSet<&String> = observations.labels.iter().collect();
+        for label in labels {
+            *self.counts.entry(label.clone()).or_default() += 1;
+        }
+
+        for (label, percentage) in &observations.required {
+            let required = self.required.entry(label.clone()).or_default();
+            *required = required.max(*percentage);
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: Coverage) {
+        for (label, count) in other.counts {
+            *self.counts.entry(label).or_default() += count;
+        }
+
+        for (label, percentage) in other.required {
+            let required = self.required.entry(label).or_default();
+            *required = required.max(percentage);
+        }
+    }
+
+    /// Produces the result of a run in which every iteration passed,
+    /// which is still a failure if any [crate::Maat::cover] requirement was not met.
+    pub(crate) fn finish(
+        self,
+        seed: u64,
+        iterations: usize,
+        elapsed: Duration,
+    ) -> Result<Stats, Failure> {
+        let mut labels: Vec<(String, usize)> = self.counts.into_iter().collect();
+        labels.sort_by(|(_, a), (_, b)| b.cmp(a));
+        let stats = Stats {
+            seed: Some(seed),
+            iterations,
+            elapsed,
+            labels,
+        };
+
+        let mut message = String::new();
+        for (label, required) in &self.required {
+            let count = stats
+                .labels
+                .iter()
+                .find(|(l, _)| l == label)
+                .map_or(0, |(_, count)| *count);
+
+            let actual = percentage(count, iterations);
+            if actual < *required {
+                writeln!(
+                    message,
+                    "\n[maat] Insufficient coverage: {label} was {actual:.1}% of tests, but {required}% is required"
+                )
+                .unwrap();
+            }
+        }
+
+        if message.is_empty() {
+            return Ok(stats);
+        }
+
+        write!(message, "\n{}", stats.distribution()).unwrap();
+        Err(Failure {
+            seed: Some(seed),
+            iteration: iterations.saturating_sub(1),
+            shrink_steps: 0,
+            shrink_time: Duration::ZERO,
+            original: Vec::new(),
+            shrunk: Vec::new(),
+            buffer: None,
+            message,
+        })
+    }
+}
+
+pub(crate) fn percentage(count: usize, iterations: usize) -> f64 {
+    if iterations == 0 {
+        0.0
+    } else {
+        100.0 * count as f64 / iterations as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check_cfg, generators::i64, Config};
+
+    #[test]
+    pub fn counts_labels() {
+        let stats: Stats = check_cfg(
+            |maat| {
+                let x = maat.generate("x", i64(0, 100));
+                maat.classify(x < 50, "small");
+                maat.classify(x >= 50, "large");
+                maat.label("any");
+                maat.label("any");
+                true
+            },
+            &Config::default().seed(1234).iterations(1000),
+        )
+        .unwrap();
+
+        assert_eq!(stats.labels[0], ("any".to_string(), 1000));
+        let small = stats.labels.iter().find(|(l, _)| l == "small").unwrap().1;
+        let large = stats.labels.iter().find(|(l, _)| l == "large").unwrap().1;
+        assert_eq!(small + large, 1000);
+    }
+
+    #[test]
+    pub fn insufficient_coverage_fails() {
+        let failure = check_cfg(
+            |maat| {
+                let x = maat.generate("x", i64(0, 100));
+                maat.cover(10.0, x == 0, "zero");
+                true
+            },
+            &Config::default().seed(1234).iterations(1000),
+        )
+        .unwrap_err();
+
+        assert!(failure.message.contains("Insufficient coverage: zero"));
+    }
+}
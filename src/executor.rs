@@ -0,0 +1,135 @@
+//! Support for `async` properties.
+//!
+//! The property is run to completion for each iteration by an [Executor],
+//! so generation, recording and shrinking all work exactly as they do
+//! for synchronous properties.
+//!
+//! ```rust
+//! # use maat::{generators::i64, property_async, BlockOn};
+//! # struct Service;
+//! # impl Service {
+//! #     async fn call(&self, x: i64) -> Result<i64, ()> { Ok(x) }
+//! # }
+//! # let service = Service;
+//! property_async(&BlockOn, async |maat| {
+//!     let x = maat.generate("x", i64(0, 100));
+//!     service.call(x).await.is_ok()
+//! });
+//! ```
+//!
+//! To use another runtime, implement [Executor] for it:
+//!
+//! ```rust,ignore
+//! struct Tokio(tokio::runtime::Runtime);
+//!
+//! impl Executor for Tokio {
+//!     fn block_on<F: Future>(&self, future: F) -> F::Output {
+//!         self.0.block_on(future)
+//!     }
+//! }
+//! ```
+
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::Thread,
+};
+
+use crate::{check_cfg, property_cfg, Config, Failure, Maat, Stats, DEFAULT_CONFIG};
+
+/// Runs a future to completion on the current thread.
+pub trait Executor {
+    fn block_on<F: Future>(&self, future: F) -> F::Output;
+}
+
+/// A minimal [Executor], which polls the future on the
+/// current thread and parks the thread while it is pending.
+pub struct BlockOn;
+
+impl Executor for BlockOn {
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        struct ThreadWaker(Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+}
+
+/// Like [crate::property], but for an `async` property.
+pub fn property_async(executor: &impl Executor, test: impl AsyncFn(&mut Maat) -> bool) {
+    property_async_cfg(executor, test, &DEFAULT_CONFIG);
+}
+
+pub fn property_async_cfg(
+    executor: &impl Executor,
+    test: impl AsyncFn(&mut Maat) -> bool,
+    cfg: &Config,
+) {
+    property_cfg(|maat| executor.block_on(test(maat)), cfg);
+}
+
+/// Like [crate::check], but for an `async` property.
+pub fn check_async(
+    executor: &impl Executor,
+    test: impl AsyncFn(&mut Maat) -> bool,
+) -> Result<Stats, Failure> {
+    check_async_cfg(executor, test, &DEFAULT_CONFIG)
+}
+
+pub fn check_async_cfg(
+    executor: &impl Executor,
+    test: impl AsyncFn(&mut Maat) -> bool,
+    cfg: &Config,
+) -> Result<Stats, Failure> {
+    check_cfg(|maat| executor.block_on(test(maat)), cfg)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generators::i64;
+
+    /// A future which is pending once before completing.
+    struct YieldNow(bool);
+
+    impl Future for YieldNow {
+        type Output = ();
+
+        fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    #[test]
+    pub fn shrinks_async_property() {
+        let failure = check_async(&BlockOn, async |maat| {
+            let x = maat.generate("x", i64(0, 100));
+            YieldNow(false).await;
+            x < 10
+        })
+        .unwrap_err();
+
+        assert_eq!(failure.shrunk[0].downcast_ref::<i64>(), Some(&10));
+    }
+}
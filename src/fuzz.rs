@@ -0,0 +1,165 @@
+//! Running properties as fuzz targets.
+//!
+//! The fuzzer's input is used in place of the RNG, so the same property
+//! can be run by [crate::property] and by `cargo fuzz`:
+//!
+//! ```rust,ignore
+//! // fuzz/fuzz_targets/parse.rs
+//! #![no_main]
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| maat::fuzz(data, parse_property));
+//! ```
+//!
+//! When an input falsifies the property, or the property panics, its values
+//! are shrunk as usual and reported by panicking, which the fuzzer records
+//! as a crash.
+//!
+//! A single input can also be checked with [Config::input], in which case
+//! the input itself is shrunk before its values are.
+
+use std::time::Instant;
+
+use crate::{
+    catch_panic, choices::BufferRng, handle_failure, make_recording, shrink_failure,
+    snapshot_recording, Config, Failure, Maat, Mode, Stats, DEFAULT_CONFIG,
+};
+
+/// Checks the property against the values generated from `data`,
+/// panicking with a description of the shrunk counterexample
+/// if it is falsified.
+pub fn fuzz(data: &[u8], test: impl Fn(&mut Maat) -> bool) {
+    fuzz_cfg(data, test, &DEFAULT_CONFIG);
+}
+
+pub fn fuzz_cfg(data: &[u8], test: impl Fn(&mut Maat) -> bool, cfg: &Config) {
+    let test = failing_on_panic(test);
+    let mode = Mode::Testing {
+        rng: &mut BufferRng::new(data),
+    };
+
+    if !test(&mut Maat::new(mode)) {
+        let failure = handle_failure(test, BufferRng::new(data), None, 0, cfg);
+        panic!("{failure}");
+    }
+}
+
+pub(crate) fn run_input(
+    test: impl Fn(&mut Maat) -> bool,
+    input: &[u8],
+    cfg: &Config,
+) -> Result<Stats, Failure> {
+    let test = failing_on_panic(test);
+    let start = Instant::now();
+    if passes(&test, input) {
+        return Ok(Stats {
+            seed: None,
+            iterations: 1,
+            elapsed: start.elapsed(),
+            labels: Vec::new(),
+        });
+    }
+
+    let original = snapshot_recording(&make_recording(&test, BufferRng::new(input)));
+    let shrink_start = Instant::now();
+    let buffer = shrink_buffer(&test, input.to_vec());
+    let buffer_shrink_time = shrink_start.elapsed();
+    let recording = make_recording(&test, BufferRng::new(&buffer));
+    let mut failure = shrink_failure(test, recording, original, None, 0, cfg);
+    failure.buffer = Some(buffer.into());
+    failure.shrink_time += buffer_shrink_time;
+    Err(failure)
+}
+
+/// Treats a panic in the property as a failure, noting its message,
+/// so that crashes are shrunk and reported like any other failure.
+fn failing_on_panic(test: impl Fn(&mut Maat) -> bool) -> impl Fn(&mut Maat) -> bool {
+    move |maat| match catch_panic(|| test(maat)) {
+        Ok(passed) => passed,
+        Err(message) => {
+            maat.note("panic", message.unwrap_or_default());
+            false
+        }
+    }
+}
+
+fn passes(test: impl Fn(&mut Maat) -> bool, buffer: &[u8]) -> bool {
+    let mode = Mode::Testing {
+        rng: &mut BufferRng::new(buffer),
+    };
+
+    test(&mut Maat::new(mode))
+}
+
+/// Shrinks a failing buffer by deleting runs of bytes and zeroing bytes,
+/// which works whatever generators are used to read it.
+fn shrink_buffer(test: impl Fn(&mut Maat) -> bool, mut buffer: Vec<u8>) -> Vec<u8> {
+    loop {
+        let mut shrank = false;
+        for size in [8, 4, 2, 1] {
+            let mut ix = 0;
+            while ix + size <= buffer.len() {
+                let mut candidate = buffer.clone();
+                candidate.drain(ix..ix + size);
+                if passes(&test, &candidate) {
+                    ix += 1;
+                } else {
+                    buffer = candidate;
+                    shrank = true;
+                }
+            }
+        }
+
+        for ix in 0..buffer.len() {
+            if buffer[ix] != 0 {
+                let mut candidate = buffer.clone();
+                candidate[ix] = 0;
+                if !passes(&test, &candidate) {
+                    buffer = candidate;
+                    shrank = true;
+                }
+            }
+        }
+
+        // an exhausted buffer reads as zeros, so trailing zeros are redundant
+        while buffer.last() == Some(&0) {
+            buffer.pop();
+        }
+
+        if !shrank {
+            return buffer;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check_cfg, generators::i64};
+
+    #[test]
+    #[should_panic(expected = "x: i64 = 50")]
+    pub fn shrinks_fuzzer_input() {
+        fuzz(&[0xff; 8], |maat| maat.generate("x", i64(0, 100)) < 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "x: i64 = 50")]
+    pub fn shrinks_panicking_fuzzer_input() {
+        fuzz(&[0xff; 8], |maat| {
+            let x = maat.generate("x", i64(0, 100));
+            assert!(x < 50, "too big");
+            true
+        });
+    }
+
+    #[test]
+    pub fn shrinks_input_buffer() {
+        let test = |maat: &mut Maat| maat.generate("x", i64(0, 100)) < 50;
+        let failure = check_cfg(test, &Config::default().input([0xff; 16])).unwrap_err();
+        assert_eq!(
+            failure.buffer.as_deref(),
+            Some(&[0, 0, 0, 0, 0, 0, 0, 0xff][..])
+        );
+        assert_eq!(failure.original[0].downcast_ref::<i64>(), Some(&99));
+        assert_eq!(failure.shrunk[0].downcast_ref::<i64>(), Some(&50));
+    }
+}
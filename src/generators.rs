@@ -1,11 +1,29 @@
-use std::{ops::RangeBounds, rc::Rc};
+use std::{cell::RefCell, ops::RangeBounds, rc::Rc, sync::Arc};
 
 use rand::{
     distributions::{DistString, Distribution},
     Rng,
 };
 
-use crate::{Generator, Maat, Mode, Shrinkable};
+use crate::{Generator, Maat, Mode, Shrinkable, Shrinker};
+
+mod deserializable;
+mod grammar;
+mod inspect;
+mod json;
+mod regex;
+mod sequences;
+mod time;
+mod unicode;
+
+pub use deserializable::deserializable;
+pub use grammar::{Grammar, Symbol};
+pub use inspect::{sample, summarize, Summary};
+pub use json::Json;
+pub use regex::string_matching;
+pub use sequences::{interleaving, permutation, subsequence};
+pub use time::{date, duration, system_time, Date};
+pub use unicode::Unicode;
 
 /// The `placeholder` generator generates an arbitrary value that
 /// doesn’t ever shrink. It is useful for generating values that are
@@ -260,14 +278,18 @@ pub fn alphanumeric() -> impl Generator<char> {
     }
 }
 
+/// The `derive` generator builds a value out of other generated values.
+///
+/// The resulting generator can be shared between threads (for use
+/// with [crate::check_parallel]) if `f` can.
 pub fn derive<T>(f: impl Fn(&mut crate::Maat) -> T + 'static) -> impl Generator<T> {
     struct G<T, F> {
-        deriver: Rc<F>,
+        deriver: Arc<F>,
         _marker: std::marker::PhantomData<T>,
     }
 
     return G {
-        deriver: Rc::new(f),
+        deriver: Arc::new(f),
         _marker: std::marker::PhantomData,
     };
 
@@ -277,7 +299,7 @@ pub fn derive<T>(f: impl Fn(&mut crate::Maat) -> T + 'static) -> impl Generator<
     {
         fn generate(&self, rng: &mut dyn rand::RngCore) -> T {
             let mode = Mode::Testing { rng };
-            (self.deriver)(&mut Maat { mode })
+            (self.deriver)(&mut Maat::new(mode))
         }
 
         fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<T> {
@@ -288,7 +310,7 @@ pub fn derive<T>(f: impl Fn(&mut crate::Maat) -> T + 'static) -> impl Generator<
             };
 
             let deriver = self.deriver.clone();
-            let value = deriver(&mut crate::Maat { mode });
+            let value = deriver(&mut crate::Maat::new(mode));
             Shrinkable {
                 value,
                 shrink: Rc::new(move |_original_value, is_valid| {
@@ -297,12 +319,10 @@ pub fn derive<T>(f: impl Fn(&mut crate::Maat) -> T + 'static) -> impl Generator<
                         let mut shrank_any = false;
                         for v in &recording {
                             while v.shrink(&mut || {
-                                is_valid(deriver(&mut Maat {
-                                    mode: Mode::Shrinking {
-                                        recording_ix: 0,
-                                        recording: &recording,
-                                    },
-                                }))
+                                is_valid(deriver(&mut Maat::new(Mode::Shrinking {
+                                    recording_ix: 0,
+                                    recording: &recording,
+                                })))
                             }) {
                                 shrank_any = true;
                             }
@@ -321,3 +341,62 @@ pub fn derive<T>(f: impl Fn(&mut crate::Maat) -> T + 'static) -> impl Generator<
         }
     }
 }
+
+/// Shrinks greedily: moves to the first of the `candidates` for the current
+/// value that `is_valid` accepts, until none are accepted.
+/// Returns the final value, if any candidate was accepted.
+pub(crate) fn shrink_candidates<T>(
+    value: &T,
+    candidates: impl Fn(&T) -> Vec<T>,
+    mut is_valid: impl FnMut(&T) -> bool,
+) -> Option<T> {
+    let mut current: Option<T> = None;
+    loop {
+        let from = current.as_ref().unwrap_or(value);
+        match candidates(from).into_iter().find(|c| is_valid(c)) {
+            Some(next) => current = Some(next),
+            None => return current,
+        }
+    }
+}
+
+/// A shrinker for values which are rendered from a derivation (such as a
+/// string from a parse tree), which shrinks the derivation instead by
+/// moving to the first of its `candidates` which is accepted.
+pub(crate) fn shrink_derivation<N, T>(
+    node: N,
+    candidates: impl Fn(&N) -> Vec<N> + 'static,
+    render: impl Fn(&N) -> T + 'static,
+) -> Shrinker<T>
+where
+    N: Clone + 'static,
+{
+    shrink_derivation_by(node, move |current, is_valid| {
+        let start = current.borrow().clone();
+        let shrunk = shrink_candidates(&start, &candidates, |candidate| {
+            let valid = is_valid(render(candidate));
+            if valid {
+                *current.borrow_mut() = candidate.clone();
+            }
+
+            valid
+        });
+
+        shrunk.is_some()
+    })
+}
+
+/// Like [shrink_derivation], but `shrink` is given the current derivation
+/// to simplify, which it must update whenever a value is accepted.
+pub(crate) fn shrink_derivation_by<N, T>(
+    node: N,
+    shrink: impl Fn(&RefCell<N>, &mut dyn FnMut(T) -> bool) -> bool + 'static,
+) -> Shrinker<T>
+where
+    N: 'static,
+{
+    // like [derive], this keeps the derivation of the most recently accepted
+    // value, which is the value that the shrinker is always given
+    let current = RefCell::new(node);
+    Rc::new(move |_value, is_valid| shrink(&current, is_valid))
+}
@@ -0,0 +1,606 @@
+//! Generating values of any type which implements [Deserialize], by
+//! deserializing them from a [Deserializer] which makes random choices.
+//!
+//! The choices made are recorded, and shrinking simplifies the recorded
+//! choices (removing them, or moving them towards zero) and deserializes
+//! again, so no type-specific shrinking code is needed.
+
+use std::{cell::RefCell, fmt::Display, marker::PhantomData};
+
+use rand::RngCore;
+use serde::{
+    de::{
+        DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+        VariantAccess, Visitor,
+    },
+    Deserializer,
+};
+
+use crate::{
+    choices::{BufferRng, ChoiceRng},
+    generators::shrink_derivation_by,
+    Generator, Shrinkable,
+};
+
+/// The maximum length of generated strings, sequences and maps.
+const MAX_LENGTH: u64 = 8;
+
+/// The nesting depth beyond which sequences and maps are empty,
+/// options are `None` and enums are their first variant.
+const MAX_DEPTH: usize = 4;
+
+/// The number of times to retry if `T` rejects the generated input.
+const MAX_ATTEMPTS: usize = 100;
+
+/// The `deserializable` generator generates any type which implements
+/// [Deserialize], by driving its implementation with random input.
+///
+/// Values which fail to deserialize (for example, because of validation
+/// in the [Deserialize] implementation) are discarded and generated again.
+/// Recursive types are only generated to a limited depth, beyond which
+/// enums are always their first variant, so the first variant of a
+/// recursive enum must not be recursive.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::deserializable, property};
+/// # use serde::Deserialize;
+/// #[derive(Clone, Debug, Deserialize)]
+/// struct Point { x: i32, y: i32, label: Option<String> }
+///
+/// # property(|maat| {
+/// let point: Point = maat.generate("point", deserializable());
+/// # true
+/// # });
+/// ```
+pub fn deserializable<T: DeserializeOwned>() -> impl Generator<T> {
+    struct G<T> {
+        _marker: PhantomData<T>,
+    }
+
+    return G {
+        _marker: PhantomData,
+    };
+
+    impl<T: DeserializeOwned> Generator<T> for G<T> {
+        fn generate(&self, rng: &mut dyn rand::RngCore) -> T {
+            generate_with_choices(rng).0
+        }
+
+        fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<T> {
+            let (value, choices) = generate_with_choices::<T>(rng);
+            Shrinkable {
+                value,
+                shrink: shrink_derivation_by(choices, |current, is_valid| {
+                    shrink_choices(current, is_valid)
+                }),
+            }
+        }
+    }
+}
+
+fn generate_with_choices<T: DeserializeOwned>(rng: &mut dyn rand::RngCore) -> (T, Vec<u64>) {
+    let mut last_error = None;
+    for _ in 0..MAX_ATTEMPTS {
+        let mut choices = ChoiceRng::new(Vec::new(), &mut *rng);
+        match T::deserialize(ChoiceDeserializer::new(&mut choices)) {
+            Ok(value) => return (value, choices.into_choices()),
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    panic!(
+        "[maat] Unable to generate a {}: {}",
+        std::any::type_name::<T>(),
+        last_error.unwrap()
+    );
+}
+
+/// Shrinks the `current` choices, first by removing choices and then by
+/// minimizing each choice in turn, keeping the choices of each accepted value.
+fn shrink_choices<T: DeserializeOwned>(
+    current: &RefCell<Vec<u64>>,
+    is_valid: &mut dyn FnMut(T) -> bool,
+) -> bool {
+    // replaying may draw extra (zero) choices, so only candidates which
+    // are still simpler (or shorter, when removing choices) are tried
+    let mut attempt = |candidate: Vec<u64>, shorter: bool| {
+        let mut zeros = BufferRng::new(&[]);
+        let mut choices = ChoiceRng::new(candidate, &mut zeros);
+        let value = T::deserialize(ChoiceDeserializer::new(&mut choices)).ok()?;
+        let used = choices.into_choices();
+        let from = current.borrow();
+        let simpler = match shorter {
+            true => used.len() < from.len(),
+            false => (used.len(), &used) < (from.len(), &*from),
+        };
+        drop(from);
+        if simpler && is_valid(value) {
+            *current.borrow_mut() = used;
+            Some(())
+        } else {
+            None
+        }
+    };
+
+    let mut shrunk = false;
+    loop {
+        let mut progress = false;
+        loop {
+            let candidates = removal_candidates(&current.borrow());
+            if !candidates.into_iter().any(|c| attempt(c, true).is_some()) {
+                break;
+            }
+
+            progress = true;
+        }
+
+        // binary search for the smallest value of each choice
+        let mut ix = 0;
+        while ix < current.borrow().len() {
+            let (mut low, mut high) = (0, current.borrow()[ix]);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                let mut candidate = current.borrow().clone();
+                candidate[ix] = mid;
+                if attempt(candidate, false).is_some() {
+                    progress = true;
+                    high = match current.borrow().get(ix) {
+                        Some(&choice) => choice.min(mid),
+                        None => break,
+                    };
+                } else {
+                    low = mid + 1;
+                }
+            }
+
+            ix += 1;
+        }
+
+        if !progress {
+            return shrunk;
+        }
+
+        shrunk = true;
+    }
+}
+
+/// The choice sequences made by removing some of `choices`.
+fn removal_candidates(choices: &[u64]) -> Vec<Vec<u64>> {
+    let mut result = Vec::new();
+
+    // removing an item from a collection means removing
+    // all of its choices, not just the first
+    for run in (1..=MAX_LENGTH as usize + 1).rev() {
+        for ix in (0..choices.len().saturating_sub(run - 1)).rev() {
+            let mut fewer = choices.to_vec();
+            fewer.drain(ix..ix + run);
+            result.push(fewer);
+        }
+    }
+
+    // removing the first item, and shortening its collection to match
+    for ix in 0..choices.len() {
+        for run in 1..=(MAX_LENGTH as usize + 1).min(choices.len() - ix - 1) {
+            if choices[ix] > 0 {
+                let mut fewer = choices.to_vec();
+                fewer[ix] -= 1;
+                fewer.drain(ix + 1..ix + 1 + run);
+                result.push(fewer);
+            }
+        }
+    }
+
+    result
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl serde::de::Error for Error {
+    fn custom<M: Display>(msg: M) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+struct ChoiceDeserializer<'c, R> {
+    choices: &'c mut ChoiceRng<R>,
+    depth: usize,
+}
+
+impl<'c, R: RngCore> ChoiceDeserializer<'c, R> {
+    fn new(choices: &'c mut ChoiceRng<R>) -> Self {
+        ChoiceDeserializer { choices, depth: 0 }
+    }
+
+    fn nested(&mut self) -> ChoiceDeserializer<'_, R> {
+        ChoiceDeserializer {
+            choices: self.choices,
+            depth: self.depth + 1,
+        }
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.choices.next_below(n)
+    }
+
+    fn unsigned(&mut self, bits: u32) -> u64 {
+        match bits {
+            64 => self.choices.next_u64(),
+            _ => self.below(1 << bits),
+        }
+    }
+
+    /// A signed value, where small choices give values close to zero.
+    fn signed(&mut self, bits: u32) -> i64 {
+        let choice = self.unsigned(bits);
+        ((choice >> 1) as i64) ^ -((choice & 1) as i64)
+    }
+
+    fn length(&mut self) -> usize {
+        if self.depth >= MAX_DEPTH {
+            0
+        } else {
+            self.below(MAX_LENGTH + 1) as usize
+        }
+    }
+
+    fn char(&mut self) -> char {
+        // small choices give letters
+        let offset = self.below(0x110000) as u32;
+        char::from_u32(('a' as u32 + offset) % 0x110000).unwrap_or('a')
+    }
+
+    fn string(&mut self) -> String {
+        (0..self.length()).map(|_| self.char()).collect()
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($method:ident, $visit:ident, $type:ty, $choice:ident, $bits:expr) => {
+        fn $method<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+            visitor.$visit(self.$choice($bits) as $type)
+        }
+    };
+}
+
+impl<'de, R: RngCore> Deserializer<'de> for ChoiceDeserializer<'_, R> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        let kinds = if self.depth >= MAX_DEPTH { 5 } else { 7 };
+        match self.below(kinds) {
+            0 => visitor.visit_unit(),
+            1 => self.deserialize_bool(visitor),
+            2 => self.deserialize_i64(visitor),
+            3 => self.deserialize_f64(visitor),
+            4 => self.deserialize_string(visitor),
+            5 => self.deserialize_seq(visitor),
+            _ => self.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_bool(self.below(2) == 1)
+    }
+
+    deserialize_integer!(deserialize_i8, visit_i8, i8, signed, 8);
+    deserialize_integer!(deserialize_i16, visit_i16, i16, signed, 16);
+    deserialize_integer!(deserialize_i32, visit_i32, i32, signed, 32);
+    deserialize_integer!(deserialize_i64, visit_i64, i64, signed, 64);
+    deserialize_integer!(deserialize_i128, visit_i128, i128, signed, 64);
+    deserialize_integer!(deserialize_u8, visit_u8, u8, unsigned, 8);
+    deserialize_integer!(deserialize_u16, visit_u16, u16, unsigned, 16);
+    deserialize_integer!(deserialize_u32, visit_u32, u32, unsigned, 32);
+    deserialize_integer!(deserialize_u64, visit_u64, u64, unsigned, 64);
+    deserialize_integer!(deserialize_u128, visit_u128, u128, unsigned, 64);
+
+    fn deserialize_f32<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f32(self.signed(32) as f32 / 16.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f64(self.signed(64) as f64 / 16.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_char(self.char())
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.string())
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        let bytes = (0..self.length()).map(|_| self.below(256) as u8).collect();
+        visitor.visit_byte_buf(bytes)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        if self.depth >= MAX_DEPTH || self.below(2) == 0 {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self.nested())
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        let remaining = self.length();
+        visitor.visit_seq(Sequence {
+            deserializer: self,
+            remaining,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(Sequence {
+            deserializer: self,
+            remaining: len,
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        let remaining = self.length();
+        visitor.visit_map(Map {
+            deserializer: self,
+            fields: None,
+            remaining,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_map(Map {
+            deserializer: self,
+            fields: Some(fields),
+            remaining: fields.len(),
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if variants.is_empty() {
+            return Err(Error(
+                "cannot generate an enum without variants".to_string(),
+            ));
+        }
+
+        let variant = match self.depth >= MAX_DEPTH {
+            true => 0,
+            false => self.below(variants.len() as u64) as u32,
+        };
+
+        visitor.visit_enum(Enum {
+            deserializer: self.nested(),
+            variant,
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+struct Sequence<'c, R> {
+    deserializer: ChoiceDeserializer<'c, R>,
+    remaining: usize,
+}
+
+impl<'de, R: RngCore> SeqAccess<'de> for Sequence<'_, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        seed.deserialize(self.deserializer.nested()).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct Map<'c, R> {
+    deserializer: ChoiceDeserializer<'c, R>,
+    /// The field names of a struct, which are used as its keys.
+    fields: Option<&'static [&'static str]>,
+    remaining: usize,
+}
+
+impl<'de, R: RngCore> MapAccess<'de> for Map<'_, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        match self.fields {
+            Some(fields) => {
+                let field = fields[fields.len() - self.remaining - 1];
+                seed.deserialize(field.into_deserializer()).map(Some)
+            }
+            None => seed.deserialize(self.deserializer.nested()).map(Some),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(self.deserializer.nested())
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+struct Enum<'c, R> {
+    deserializer: ChoiceDeserializer<'c, R>,
+    variant: u32,
+}
+
+impl<'de, 'c, R: RngCore> EnumAccess<'de> for Enum<'c, R> {
+    type Error = Error;
+    type Variant = ChoiceDeserializer<'c, R>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self.deserializer))
+    }
+}
+
+impl<'de, R: RngCore> VariantAccess<'de> for ChoiceDeserializer<'_, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_struct("", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, generators::sample};
+    use serde::Deserialize;
+
+    #[derive(Clone, Debug, Deserialize, PartialEq)]
+    enum Shape {
+        Circle { radius: u32 },
+        Rectangle(u32, u32),
+        Empty,
+    }
+
+    #[derive(Clone, Debug, Deserialize, PartialEq)]
+    struct Drawing {
+        name: String,
+        shapes: Vec<Shape>,
+        scale: Option<i16>,
+    }
+
+    #[derive(Clone, Debug, Deserialize, PartialEq)]
+    enum Expr {
+        Lit(i64),
+        Add(Box<Expr>, Box<Expr>),
+    }
+
+    #[test]
+    pub fn generates_structs_and_enums() {
+        let drawings: Vec<Drawing> = sample(deserializable(), 100, 1234);
+        assert!(drawings.iter().any(|d| d.shapes.len() > 1));
+        assert!(drawings.iter().any(|d| d.scale.is_some()));
+    }
+
+    #[test]
+    pub fn generates_recursive_enums() {
+        let exprs: Vec<Expr> = sample(deserializable(), 200, 1);
+        assert!(exprs
+            .iter()
+            .any(|e| matches!(e, Expr::Add(a, _) if matches!(**a, Expr::Add(..)))));
+    }
+
+    #[test]
+    pub fn shrinks_choices() {
+        let failure = check(|maat| {
+            let drawing: Drawing = maat.generate("drawing", deserializable());
+            !drawing
+                .shapes
+                .iter()
+                .any(|s| matches!(s, Shape::Rectangle(w, _) if *w > 10))
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            failure.shrunk[0].downcast_ref::<Drawing>().unwrap(),
+            &Drawing {
+                name: String::new(),
+                shapes: vec![Shape::Rectangle(11, 0)],
+                scale: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,713 @@
+//! Generating text from a context-free grammar.
+//!
+//! A value is generated as a derivation tree, choosing an alternative for
+//! each rule until only terminals remain. Alternatives which cannot finish
+//! within the depth limit are not chosen, so generation always terminates.
+//! Shrinking replaces subtrees with the minimal derivation of their rule,
+//! or with smaller subtrees derived from the same rule.
+
+use std::{collections::HashMap, rc::Rc};
+
+use rand::Rng;
+
+use crate::{generators::shrink_derivation, Generator, Shrinkable};
+
+/// The default for [Grammar::max_depth].
+const DEFAULT_MAX_DEPTH: usize = 12;
+
+/// A context-free grammar, built up rule by rule or parsed from BNF.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::{Grammar, Symbol}, property};
+/// let grammar = Grammar::new("expr")
+///     .rule("expr", [Symbol::rule("term"), Symbol::terminal("+"), Symbol::rule("expr")])
+///     .rule("expr", [Symbol::rule("term")])
+///     .weighted_rule("term", 3, [Symbol::terminal("1")])
+///     .rule("term", [Symbol::terminal("("), Symbol::rule("expr"), Symbol::terminal(")")]);
+///
+/// # property(|maat| {
+/// let expr = maat.generate("expr", grammar.generator());
+/// # true
+/// # });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Grammar {
+    start: String,
+    rules: Vec<Rule>,
+    max_depth: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    name: String,
+    alternatives: Vec<Alternative>,
+}
+
+#[derive(Clone, Debug)]
+struct Alternative {
+    weight: u32,
+    symbols: Vec<Symbol>,
+}
+
+/// One symbol of an alternative in a [Grammar].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Symbol {
+    /// Text which appears literally in the output.
+    Terminal(String),
+    /// A reference to the rule with this name.
+    Rule(String),
+}
+
+impl Symbol {
+    pub fn terminal(text: impl Into<String>) -> Symbol {
+        Symbol::Terminal(text.into())
+    }
+
+    pub fn rule(name: impl Into<String>) -> Symbol {
+        Symbol::Rule(name.into())
+    }
+}
+
+impl Grammar {
+    /// Creates an empty grammar, which generates from the rule named `start`.
+    pub fn new(start: impl Into<String>) -> Grammar {
+        Grammar {
+            start: start.into(),
+            rules: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Adds an alternative to the rule named `name`.
+    pub fn rule(self, name: impl Into<String>, symbols: impl IntoIterator<Item = Symbol>) -> Self {
+        self.weighted_rule(name, 1, symbols)
+    }
+
+    /// Adds an alternative to the rule named `name`, which is chosen
+    /// in proportion to its `weight` (the default weight is 1).
+    pub fn weighted_rule(
+        mut self,
+        name: impl Into<String>,
+        weight: u32,
+        symbols: impl IntoIterator<Item = Symbol>,
+    ) -> Self {
+        let name = name.into();
+        let alternative = Alternative {
+            weight,
+            symbols: symbols.into_iter().collect(),
+        };
+
+        match self.rules.iter_mut().find(|rule| rule.name == name) {
+            Some(rule) => rule.alternatives.push(alternative),
+            None => self.rules.push(Rule {
+                name,
+                alternatives: vec![alternative],
+            }),
+        }
+
+        self
+    }
+
+    /// Sets the maximum depth of derivation trees. Deeper trees are only
+    /// generated if the start rule cannot be derived at all within this depth.
+    pub fn max_depth(self, max_depth: usize) -> Self {
+        Grammar { max_depth, ..self }
+    }
+
+    /// Parses a grammar in BNF, with the EBNF extensions `[optional]`,
+    /// `{repeated}` and `(grouped)`. The first rule is the start rule.
+    ///
+    /// Rules are defined with `::=` or `=`, and may optionally end with `;`.
+    /// Terminals are quoted with `"` or `'`; rule names may be bare
+    /// identifiers or written as `<name>`.
+    ///
+    /// ```text
+    /// expr ::= term "+" expr | term
+    /// term ::= "(" expr ")" | digit {digit}
+    /// digit ::= "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9"
+    /// ```
+    pub fn parse(bnf: &str) -> Result<Grammar, String> {
+        let tokens = tokenize(bnf)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            grammar: Grammar::new(""),
+            generated: 0,
+        };
+
+        while parser.position < tokens.len() {
+            let name = match parser.next() {
+                Some(Token::Name(name)) => name.clone(),
+                other => return Err(format!("expected a rule name, found {other:?}")),
+            };
+
+            if parser.next() != Some(&Token::Define) {
+                return Err(format!("expected `::=` after {name}"));
+            }
+
+            if parser.grammar.start.is_empty() {
+                parser.grammar.start = name.clone();
+            }
+
+            for symbols in parser.alternatives()? {
+                parser.grammar = parser.grammar.rule(&name, symbols);
+            }
+
+            if parser.peek() == Some(&Token::Semicolon) {
+                parser.position += 1;
+            }
+        }
+
+        Ok(parser.grammar)
+    }
+
+    /// A generator of strings derived from the grammar.
+    ///
+    /// # Panics
+    /// If a rule is referenced but not defined, or if
+    /// some rule can never finish deriving a string.
+    pub fn generator(&self) -> impl Generator<String> {
+        struct G {
+            compiled: Rc<Compiled>,
+        }
+
+        return G {
+            compiled: Rc::new(Compiled::new(self).unwrap_or_else(|e| panic!("[maat] {e}"))),
+        };
+
+        impl Generator<String> for G {
+            fn generate(&self, rng: &mut dyn rand::RngCore) -> String {
+                self.compiled.render(&self.compiled.derive(rng))
+            }
+
+            fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<String> {
+                let tree = self.compiled.derive(rng);
+                let value = self.compiled.render(&tree);
+                let (compiled, render_compiled) = (self.compiled.clone(), self.compiled.clone());
+                Shrinkable {
+                    shrink: shrink_derivation(
+                        tree,
+                        move |tree| compiled.candidates(tree),
+                        move |tree| render_compiled.render(tree),
+                    ),
+                    value,
+                }
+            }
+        }
+    }
+}
+
+/// A grammar with rule names resolved, and the depth
+/// needed to finish deriving each alternative.
+struct Compiled {
+    start: usize,
+    max_depth: usize,
+    rules: Vec<Vec<CompiledAlternative>>,
+    /// The alternative of each rule with the smallest depth.
+    minimal: Vec<usize>,
+}
+
+struct CompiledAlternative {
+    weight: u32,
+    symbols: Vec<CompiledSymbol>,
+    /// The smallest depth of a tree derived using this alternative.
+    depth: usize,
+}
+
+enum CompiledSymbol {
+    Terminal(String),
+    Rule(usize),
+}
+
+/// A derivation tree: the alternative chosen for a rule,
+/// and the trees for each rule referenced by that alternative.
+#[derive(Clone, Debug, PartialEq)]
+struct Tree {
+    rule: usize,
+    alternative: usize,
+    children: Vec<Tree>,
+}
+
+impl Compiled {
+    fn new(grammar: &Grammar) -> Result<Compiled, String> {
+        let index: HashMap<&str, usize> = grammar
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(ix, rule)| (rule.name.as_str(), ix))
+            .collect();
+
+        let resolve = |name: &str| {
+            index
+                .get(name)
+                .copied()
+                .ok_or_else(|| format!("Grammar rule {name} is not defined"))
+        };
+
+        let mut rules = Vec::new();
+        for rule in &grammar.rules {
+            let mut alternatives = Vec::new();
+            for alternative in &rule.alternatives {
+                let symbols = alternative
+                    .symbols
+                    .iter()
+                    .map(|symbol| match symbol {
+                        Symbol::Terminal(text) => Ok(CompiledSymbol::Terminal(text.clone())),
+                        Symbol::Rule(name) => resolve(name).map(CompiledSymbol::Rule),
+                    })
+                    .collect::<Result<_, String>>()?;
+
+                alternatives.push(CompiledAlternative {
+                    weight: alternative.weight,
+                    symbols,
+                    depth: usize::MAX,
+                });
+            }
+
+            rules.push(alternatives);
+        }
+
+        // find the depth of each alternative by iterating to a fixpoint
+        let mut rule_depths = vec![usize::MAX; rules.len()];
+        loop {
+            let mut changed = false;
+            for (rule, alternatives) in rules.iter_mut().enumerate() {
+                for alternative in alternatives.iter_mut() {
+                    let depth = alternative
+                        .symbols
+                        .iter()
+                        .map(|symbol| match symbol {
+                            CompiledSymbol::Terminal(_) => 0,
+                            CompiledSymbol::Rule(rule) => rule_depths[*rule],
+                        })
+                        .max()
+                        .unwrap_or(0)
+                        .saturating_add(1);
+
+                    if depth < alternative.depth {
+                        alternative.depth = depth;
+                        changed = true;
+                    }
+
+                    rule_depths[rule] = rule_depths[rule].min(depth);
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        if let Some(ix) = rule_depths.iter().position(|depth| *depth == usize::MAX) {
+            let name = &grammar.rules[ix].name;
+            return Err(format!(
+                "Grammar rule {name} can never finish deriving a string"
+            ));
+        }
+
+        let minimal = rules
+            .iter()
+            .map(|alternatives| {
+                (0..alternatives.len())
+                    .min_by_key(|ix| alternatives[*ix].depth)
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        Ok(Compiled {
+            start: resolve(&grammar.start)?,
+            max_depth: grammar.max_depth.max(rule_depths[resolve(&grammar.start)?]),
+            rules,
+            minimal,
+        })
+    }
+
+    fn derive(&self, rng: &mut dyn rand::RngCore) -> Tree {
+        self.derive_rule(self.start, self.max_depth, rng)
+    }
+
+    fn derive_rule(&self, rule: usize, depth: usize, rng: &mut dyn rand::RngCore) -> Tree {
+        let alternatives = &self.rules[rule];
+        let total: u32 = alternatives
+            .iter()
+            .filter(|a| a.depth <= depth)
+            .map(|a| a.weight)
+            .sum();
+
+        let alternative = if total == 0 {
+            self.minimal[rule]
+        } else {
+            let mut choice = rng.gen_range(0..total);
+            alternatives
+                .iter()
+                .position(|a| {
+                    if a.depth > depth {
+                        return false;
+                    }
+
+                    if choice < a.weight {
+                        true
+                    } else {
+                        choice -= a.weight;
+                        false
+                    }
+                })
+                .unwrap()
+        };
+
+        let children = self
+            .rules_referenced(rule, alternative)
+            .map(|child| self.derive_rule(child, depth.saturating_sub(1), rng))
+            .collect();
+
+        Tree {
+            rule,
+            alternative,
+            children,
+        }
+    }
+
+    fn minimal_tree(&self, rule: usize) -> Tree {
+        let alternative = self.minimal[rule];
+        Tree {
+            rule,
+            alternative,
+            children: self
+                .rules_referenced(rule, alternative)
+                .map(|child| self.minimal_tree(child))
+                .collect(),
+        }
+    }
+
+    fn rules_referenced(
+        &self,
+        rule: usize,
+        alternative: usize,
+    ) -> impl Iterator<Item = usize> + '_ {
+        self.rules[rule][alternative]
+            .symbols
+            .iter()
+            .filter_map(|symbol| match symbol {
+                CompiledSymbol::Terminal(_) => None,
+                CompiledSymbol::Rule(rule) => Some(*rule),
+            })
+    }
+
+    /// The trees which are one step simpler than `tree`. Every candidate is
+    /// smaller by [Compiled::size], so shrinking always terminates.
+    fn candidates(&self, tree: &Tree) -> Vec<Tree> {
+        let mut result = vec![self.minimal_tree(tree.rule)];
+
+        // replace the tree with a smaller one for the same rule
+        let mut descendants = Vec::new();
+        for child in &tree.children {
+            collect_rule(child, tree.rule, &mut descendants);
+        }
+
+        result.extend(descendants.into_iter().cloned());
+
+        // switch to another alternative which keeps some of the children
+        for alternative in 0..self.rules[tree.rule].len() {
+            let mut children = tree.children.iter();
+            let kept: Option<Vec<Tree>> = self
+                .rules_referenced(tree.rule, alternative)
+                .map(|rule| children.find(|child| child.rule == rule).cloned())
+                .collect();
+
+            if let Some(children) = kept {
+                result.push(Tree {
+                    alternative,
+                    children,
+                    ..*tree
+                });
+            }
+        }
+
+        for (ix, child) in tree.children.iter().enumerate() {
+            for candidate in self.candidates(child) {
+                let mut children = tree.children.clone();
+                children[ix] = candidate;
+                result.push(Tree { children, ..*tree });
+            }
+        }
+
+        let original = size(tree);
+        result.retain(|candidate| size(candidate) < original);
+        result
+    }
+
+    fn render(&self, tree: &Tree) -> String {
+        let mut result = String::new();
+        self.render_into(tree, &mut result);
+        result
+    }
+
+    fn render_into(&self, tree: &Tree, result: &mut String) {
+        let mut children = tree.children.iter();
+        for symbol in &self.rules[tree.rule][tree.alternative].symbols {
+            match symbol {
+                CompiledSymbol::Terminal(text) => result.push_str(text),
+                CompiledSymbol::Rule(_) => self.render_into(children.next().unwrap(), result),
+            }
+        }
+    }
+}
+
+/// The number of nodes in `tree`, then the alternatives of its nodes in
+/// pre-order. There are finitely many trees smaller than any tree.
+fn size(tree: &Tree) -> (usize, Vec<usize>) {
+    fn alternatives(tree: &Tree, result: &mut Vec<usize>) {
+        result.push(tree.alternative);
+        for child in &tree.children {
+            alternatives(child, result);
+        }
+    }
+
+    let mut result = Vec::new();
+    alternatives(tree, &mut result);
+    (result.len(), result)
+}
+
+/// Collects the subtrees of `tree` which are derived from `rule`.
+fn collect_rule<'t>(tree: &'t Tree, rule: usize, result: &mut Vec<&'t Tree>) {
+    if tree.rule == rule {
+        result.push(tree);
+    }
+
+    for child in &tree.children {
+        collect_rule(child, rule, result);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Terminal(String),
+    Define,
+    Pipe,
+    Semicolon,
+    Open(char),
+    Close(char),
+}
+
+fn tokenize(bnf: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = bnf.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => {}
+            '|' => tokens.push(Token::Pipe),
+            ';' => tokens.push(Token::Semicolon),
+            '=' => tokens.push(Token::Define),
+            ':' => {
+                if chars.next() != Some(':') || chars.next() != Some('=') {
+                    return Err("expected `::=`".to_string());
+                }
+
+                tokens.push(Token::Define);
+            }
+            '[' | '{' | '(' => tokens.push(Token::Open(c)),
+            ']' | '}' | ')' => tokens.push(Token::Close(c)),
+            '"' | '\'' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => text.extend(chars.next()),
+                        Some(end) if end == c => break,
+                        Some(other) => text.push(other),
+                        None => return Err(format!("unterminated terminal {c}{text}")),
+                    }
+                }
+
+                tokens.push(Token::Terminal(text));
+            }
+            '<' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('>') => break,
+                        Some(other) => name.push(other),
+                        None => return Err(format!("unterminated rule name <{name}")),
+                    }
+                }
+
+                tokens.push(Token::Name(name));
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_alphanumeric() || next == '_' || next == '-' {
+                        name.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                tokens.push(Token::Name(name));
+            }
+            _ => return Err(format!("unexpected character {c:?}")),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'t> {
+    tokens: &'t [Token],
+    position: usize,
+    grammar: Grammar,
+    /// The number of rules generated for EBNF extensions.
+    generated: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        self.position += 1;
+        self.tokens.get(self.position - 1)
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Symbol>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some(&Token::Pipe) {
+            self.position += 1;
+            alternatives.push(self.sequence()?);
+        }
+
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Symbol>, String> {
+        let mut symbols = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Name(name)) => {
+                    // a name followed by `::=` starts the next rule
+                    if self.tokens.get(self.position + 1) == Some(&Token::Define) {
+                        return Ok(symbols);
+                    }
+
+                    symbols.push(Symbol::Rule(name.clone()));
+                    self.position += 1;
+                }
+                Some(Token::Terminal(text)) => {
+                    // `""` is the empty string, not a symbol
+                    if !text.is_empty() {
+                        symbols.push(Symbol::Terminal(text.clone()));
+                    }
+
+                    self.position += 1;
+                }
+                Some(Token::Open(open)) => {
+                    let open = *open;
+                    self.position += 1;
+                    let alternatives = self.alternatives()?;
+                    let close = match open {
+                        '[' => ']',
+                        '{' => '}',
+                        _ => ')',
+                    };
+
+                    if self.next() != Some(&Token::Close(close)) {
+                        return Err(format!("expected `{close}`"));
+                    }
+
+                    symbols.push(self.extension(open, alternatives));
+                }
+                _ => return Ok(symbols),
+            }
+        }
+    }
+
+    /// Adds a rule for an EBNF extension, returning a reference to it.
+    fn extension(&mut self, open: char, alternatives: Vec<Vec<Symbol>>) -> Symbol {
+        self.generated += 1;
+        let name = format!("{open}{}", self.generated);
+        let mut grammar = std::mem::replace(&mut self.grammar, Grammar::new(""));
+
+        // `[x]` and `{x}` may also be empty
+        if open != '(' {
+            grammar = grammar.rule(&name, Vec::new());
+        }
+
+        for mut symbols in alternatives {
+            // `{x}` is `x` followed by more repetitions
+            if open == '{' {
+                symbols.push(Symbol::Rule(name.clone()));
+            }
+
+            grammar = grammar.rule(&name, symbols);
+        }
+
+        self.grammar = grammar;
+        Symbol::Rule(name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, generators::sample};
+
+    const ARITHMETIC: &str = r#"
+        expr ::= term "+" expr | term
+        term ::= "(" expr ")" | digit {digit}
+        digit ::= "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9"
+    "#;
+
+    fn balanced(s: &str) -> bool {
+        let mut depth = 0i32;
+        for c in s.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+
+            if depth < 0 {
+                return false;
+            }
+        }
+
+        depth == 0
+    }
+
+    #[test]
+    pub fn generates_from_bnf() {
+        let grammar = Grammar::parse(ARITHMETIC).unwrap().max_depth(6);
+        for value in sample(grammar.generator(), 1000, 1234) {
+            assert!(balanced(&value), "{value:?} is unbalanced");
+            assert!(!value.is_empty() && !value.ends_with('+'));
+        }
+    }
+
+    #[test]
+    pub fn shrinks_to_minimal_derivation() {
+        let grammar = Grammar::parse(ARITHMETIC).unwrap();
+        let failure = check(|maat| {
+            let expr = maat.generate("expr", grammar.generator());
+            !expr.contains("(")
+        })
+        .unwrap_err();
+
+        assert_eq!(failure.shrunk[0].downcast_ref::<String>().unwrap(), "(0)");
+    }
+
+    #[test]
+    pub fn shrinking_terminates_between_alternatives() {
+        let grammar = Grammar::parse(r#"s ::= t t | t; t ::= "x""#).unwrap();
+        let failure = check(|maat| maat.generate("s", grammar.generator()).is_empty()).unwrap_err();
+        assert_eq!(failure.shrunk[0].downcast_ref::<String>().unwrap(), "x");
+    }
+
+    #[test]
+    pub fn rejects_undefined_rules() {
+        let grammar = Grammar::new("a").rule("a", [Symbol::rule("b")]);
+        assert!(Compiled::new(&grammar).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Tools for checking what a generator produces,
+//! before building properties on top of it.
+
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    fmt::{Debug, Display},
+};
+
+use rand::SeedableRng;
+
+use crate::{Generator, RNG};
+
+/// Generates `n` values, reproducibly for a given `seed`.
+pub fn sample<T>(generator: impl Generator<T>, n: usize, seed: u64) -> Vec<T> {
+    let mut rng = RNG::seed_from_u64(seed);
+    (0..n).map(|_| generator.generate(&mut rng)).collect()
+}
+
+/// Summarizes `n` values from the generator, as generated for `seed`.
+///
+/// The `size` of each value is used for the range and histogram: this might
+/// be the value itself for numbers, or the length of a collection.
+///
+/// # Example
+/// ```rust
+/// # use maat::generators::{i64, summarize};
+/// println!("{}", summarize(i64(0, 100), 1000, 0, |&x| x as f64));
+/// ```
+pub fn summarize<T: Debug>(
+    generator: impl Generator<T>,
+    n: usize,
+    seed: u64,
+    size: impl Fn(&T) -> f64,
+) -> Summary {
+    let mut rng = RNG::seed_from_u64(seed);
+    let mut distinct = HashSet::new();
+    let mut examples = Vec::new();
+    let mut sizes = Vec::new();
+    let mut depths = Vec::new();
+    let mut breadths = Vec::new();
+    for _ in 0..n {
+        // shrinkers may keep state as they shrink, so each measurement
+        // uses its own shrinkable, generated from the same RNG state
+        let mut breadth_rng = rng.clone();
+        let shrinkable = generator.generate_shrinkable(&mut rng);
+        let debug = format!("{:?}", shrinkable.value);
+        if distinct.insert(debug.clone()) && examples.len() < 5 {
+            examples.push(debug);
+        }
+
+        sizes.push(size(&shrinkable.value));
+
+        // the first candidate is always accepted,
+        // so this follows the path that shrinking would take
+        // if the property kept failing
+        let depth = Cell::new(0);
+        (shrinkable.shrink)(&shrinkable.value, &mut |_| {
+            depth.set(depth.get() + 1);
+            depth.get() < MAX_SHRINK_DEPTH
+        });
+        depths.push(depth.get());
+
+        // every candidate is rejected, so this counts
+        // the candidates offered for the original value
+        let shrinkable = generator.generate_shrinkable(&mut breadth_rng);
+        let breadth = Cell::new(0);
+        (shrinkable.shrink)(&shrinkable.value, &mut |_| {
+            breadth.set(breadth.get() + 1);
+            false
+        });
+        breadths.push(breadth.get());
+    }
+
+    Summary {
+        samples: n,
+        duplicates: 100.0 * (n - distinct.len()) as f64 / n.max(1) as f64,
+        sizes,
+        depths,
+        breadths,
+        examples,
+    }
+}
+
+/// The limit on the number of shrink steps followed by [summarize].
+const MAX_SHRINK_DEPTH: usize = 10_000;
+
+/// The result of [summarize], which is displayed as a short report.
+pub struct Summary {
+    pub samples: usize,
+    /// The percentage of values which were generated more than once.
+    pub duplicates: f64,
+    /// The size of each value.
+    pub sizes: Vec<f64>,
+    /// The number of shrink steps taken from each value,
+    /// if every shrink was accepted.
+    pub depths: Vec<usize>,
+    /// The number of shrink candidates offered for each value,
+    /// if every shrink was rejected.
+    pub breadths: Vec<usize>,
+    /// Some distinct values, in the order they were generated.
+    pub examples: Vec<String>,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "samples:    {}", self.samples)?;
+        writeln!(f, "duplicates: {:.1}%", self.duplicates)?;
+        writeln!(f, "size:       {}", range(&self.sizes))?;
+        write_histogram(f, &self.sizes)?;
+        let depths: Vec<f64> = self.depths.iter().map(|&d| d as f64).collect();
+        let breadths: Vec<f64> = self.breadths.iter().map(|&b| b as f64).collect();
+        writeln!(f, "shrink depth:   {}", range(&depths))?;
+        writeln!(f, "shrink breadth: {}", range(&breadths))?;
+        writeln!(f, "examples:")?;
+        for example in &self.examples {
+            writeln!(f, "  {example}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn range(values: &[f64]) -> String {
+    if values.is_empty() {
+        return "-".to_string();
+    }
+
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    format!("min {min}, mean {mean:.1}, max {max}")
+}
+
+fn write_histogram(f: &mut std::fmt::Formatter<'_>, values: &[f64]) -> std::fmt::Result {
+    const BUCKETS: usize = 10;
+    const WIDTH: usize = 40;
+
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if values.is_empty() || min == max {
+        return Ok(());
+    }
+
+    let width = (max - min) / BUCKETS as f64;
+    let mut counts = [0; BUCKETS];
+    for value in values {
+        let bucket = ((value - min) / width) as usize;
+        counts[bucket.min(BUCKETS - 1)] += 1;
+    }
+
+    for (ix, count) in counts.iter().enumerate() {
+        let start = min + width * ix as f64;
+        let percentage = 100.0 * *count as f64 / values.len() as f64;
+        let bar = "#".repeat(WIDTH * count / values.len());
+        writeln!(f, "  {start:>10.1} | {bar:<WIDTH$} {percentage:.1}%")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generators::{derive, i64, u8};
+
+    #[test]
+    pub fn samples_are_reproducible() {
+        let first = sample(i64(0, 1000), 100, 1234);
+        assert_eq!(first, sample(i64(0, 1000), 100, 1234));
+        assert!(first.iter().all(|x| (0..1000).contains(x)));
+    }
+
+    #[test]
+    pub fn summary_counts_duplicates() {
+        let summary = summarize(u8(0, 10), 1000, 1234, |&x| x as f64);
+        assert_eq!(summary.duplicates, 99.0);
+        assert_eq!(summary.examples.len(), 5);
+        assert!(summary.depths.iter().all(|&d| d < 10));
+        assert!(summary.to_string().contains("duplicates: 99.0%"));
+    }
+
+    #[test]
+    pub fn summary_measures_stateful_shrinkers() {
+        let pairs = derive(|maat| {
+            let a = maat.generate("a", i64(1, 1000));
+            let b = maat.generate("b", i64(1, 1000));
+            (a, b)
+        });
+
+        let summary = summarize(pairs, 100, 1234, |&(a, b)| (a + b) as f64);
+        assert!(summary.depths.iter().all(|&d| d > 0));
+        assert!(summary.breadths.iter().all(|&b| b > 0));
+    }
+}
@@ -0,0 +1,753 @@
+//! Generating JSON values, optionally conforming to a JSON Schema.
+//!
+//! Shrinking proposes structurally simpler values (fewer fields and items,
+//! scalars closer to zero or empty) and keeps only those which still
+//! conform to the schema.
+
+use std::rc::Rc;
+
+use rand::{distributions::DistString, Rng};
+use serde_json::{Map, Number, Value};
+
+use crate::{generators::shrink_candidates, Generator, Shrinkable};
+
+/// Options for generating [Value]s.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::Json, property};
+/// # use serde_json::json;
+/// # property(|maat| {
+/// let request = maat.generate("request", Json::schema(&json!({
+///     "type": "object",
+///     "properties": {
+///         "id": { "type": "integer", "minimum": 1 },
+///         "tags": { "type": "array", "items": { "type": "string" }, "maxItems": 3 },
+///     },
+///     "required": ["id"],
+/// })).unwrap().generator());
+/// # true
+/// # });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Json {
+    max_depth: usize,
+    max_length: usize,
+    keys: Option<Vec<String>>,
+    numbers: (f64, f64),
+    schema: Schema,
+}
+
+/// The supported subset of JSON Schema. Bounds which are not
+/// given are filled in from the [Json] options when generating.
+#[derive(Clone, Debug)]
+enum Schema {
+    Any,
+    Null,
+    Boolean,
+    Integer {
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    Number {
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    String {
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+    },
+    Array {
+        items: Box<Schema>,
+        min_items: Option<usize>,
+        max_items: Option<usize>,
+    },
+    Object {
+        properties: Vec<(String, Schema)>,
+        required: Vec<String>,
+    },
+    Enum(Vec<Value>),
+    OneOf(Vec<Schema>),
+}
+
+impl Default for Json {
+    fn default() -> Self {
+        Json::new()
+    }
+}
+
+impl Json {
+    /// Options for generating arbitrary values.
+    pub fn new() -> Json {
+        Json {
+            max_depth: 3,
+            max_length: 5,
+            keys: None,
+            numbers: (-1000.0, 1000.0),
+            schema: Schema::Any,
+        }
+    }
+
+    /// Options for generating values which conform to `schema`.
+    ///
+    /// The keywords `type`, `enum`, `const`, `anyOf`, `oneOf`, `minimum`,
+    /// `maximum`, `minLength`, `maxLength`, `items`, `minItems`, `maxItems`,
+    /// `properties` and `required` are supported; any others are ignored.
+    /// Objects only have the properties listed in `properties` or `required`.
+    ///
+    /// Returns an error if no value can satisfy the bounds, such as when
+    /// `minimum` is greater than `maximum`.
+    pub fn schema(schema: &Value) -> Result<Json, String> {
+        Ok(Json {
+            schema: parse_schema(schema)?,
+            ..Json::new()
+        })
+    }
+
+    /// Sets the maximum nesting of arrays and objects
+    /// in unconstrained values.
+    pub fn max_depth(self, max_depth: usize) -> Self {
+        Json { max_depth, ..self }
+    }
+
+    /// Sets the maximum length of strings, arrays and objects,
+    /// where the schema does not give one.
+    pub fn max_length(self, max_length: usize) -> Self {
+        Json { max_length, ..self }
+    }
+
+    /// Sets the keys used for unconstrained objects.
+    /// By default keys are random alphanumeric strings.
+    pub fn keys(self, keys: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Json {
+            keys: Some(keys.into_iter().map(Into::into).collect()),
+            ..self
+        }
+    }
+
+    /// Sets the range of numbers, where the schema does not give one.
+    /// If `min` is greater than `max`, only `min` is generated.
+    pub fn numbers(self, min: f64, max: f64) -> Self {
+        Json {
+            numbers: (min, max),
+            ..self
+        }
+    }
+
+    pub fn generator(&self) -> impl Generator<Value> {
+        struct G {
+            json: Rc<Json>,
+        }
+
+        return G {
+            json: Rc::new(self.clone()),
+        };
+
+        impl Generator<Value> for G {
+            fn generate(&self, rng: &mut dyn rand::RngCore) -> Value {
+                self.json
+                    .generate(&self.json.schema, self.json.max_depth, rng)
+            }
+
+            fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<Value> {
+                let json = self.json.clone();
+                Shrinkable {
+                    value: self.generate(rng),
+                    shrink: Rc::new(move |value, is_valid| {
+                        let candidates = |value: &Value| candidates(&json.schema, value);
+                        shrink_candidates(value, candidates, |c| is_valid(c.clone())).is_some()
+                    }),
+                }
+            }
+        }
+    }
+
+    fn generate(&self, schema: &Schema, depth: usize, rng: &mut dyn rand::RngCore) -> Value {
+        match schema {
+            Schema::Any => {
+                let kinds = if depth == 0 { 4 } else { 6 };
+                let schema = match rng.gen_range(0..kinds) {
+                    0 => Schema::Null,
+                    1 => Schema::Boolean,
+                    2 if rng.gen() => Schema::Integer {
+                        min: None,
+                        max: None,
+                    },
+                    2 => Schema::Number {
+                        min: None,
+                        max: None,
+                    },
+                    3 => Schema::String {
+                        min_length: None,
+                        max_length: None,
+                    },
+                    4 => Schema::Array {
+                        items: Box::new(Schema::Any),
+                        min_items: None,
+                        max_items: None,
+                    },
+                    _ => return self.generate_any_object(depth, rng),
+                };
+
+                self.generate(&schema, depth.saturating_sub(1), rng)
+            }
+            Schema::Null => Value::Null,
+            Schema::Boolean => Value::Bool(rng.gen()),
+            Schema::Integer { min, max } => {
+                let (min, max) = self.bounds(*min, *max);
+                let (min, max) = (min.ceil() as i64, max.floor() as i64);
+                Value::from(rng.gen_range(min..=max.max(min)))
+            }
+            Schema::Number { min, max } => {
+                let (min, max) = self.bounds(*min, *max);
+                Number::from_f64(rng.gen_range(min..=max.max(min)))
+                    .map_or(Value::Null, Value::Number)
+            }
+            Schema::String {
+                min_length,
+                max_length,
+            } => {
+                let length = self.length(*min_length, *max_length, rng);
+                Value::String(rand::distributions::Alphanumeric.sample_string(rng, length))
+            }
+            Schema::Array {
+                items,
+                min_items,
+                max_items,
+            } => {
+                let length = self.length(*min_items, *max_items, rng);
+                Value::Array(
+                    (0..length)
+                        .map(|_| self.generate(items, depth, rng))
+                        .collect(),
+                )
+            }
+            Schema::Object {
+                properties,
+                required,
+            } => {
+                let mut object = Map::new();
+                for (name, schema) in properties {
+                    if required.contains(name) || rng.gen() {
+                        object.insert(name.clone(), self.generate(schema, depth, rng));
+                    }
+                }
+
+                Value::Object(object)
+            }
+            Schema::Enum(values) => values[rng.gen_range(0..values.len())].clone(),
+            Schema::OneOf(schemas) => {
+                let schema = &schemas[rng.gen_range(0..schemas.len())];
+                self.generate(schema, depth, rng)
+            }
+        }
+    }
+
+    fn generate_any_object(&self, depth: usize, rng: &mut dyn rand::RngCore) -> Value {
+        let mut object = Map::new();
+        match &self.keys {
+            Some(keys) => {
+                for key in keys {
+                    if rng.gen() {
+                        object.insert(key.clone(), self.generate(&Schema::Any, depth - 1, rng));
+                    }
+                }
+            }
+            None => {
+                for _ in 0..rng.gen_range(0..=self.max_length) {
+                    let length = rng.gen_range(1..=6);
+                    let key = rand::distributions::Alphanumeric.sample_string(rng, length);
+                    object.insert(key, self.generate(&Schema::Any, depth - 1, rng));
+                }
+            }
+        }
+
+        Value::Object(object)
+    }
+
+    /// Fills in missing bounds from the configured range of numbers.
+    fn bounds(&self, min: Option<f64>, max: Option<f64>) -> (f64, f64) {
+        let width = self.numbers.1 - self.numbers.0;
+        match (min, max) {
+            (Some(min), Some(max)) => (min, max),
+            (Some(min), None) => (min, min + width),
+            (None, Some(max)) => (max - width, max),
+            (None, None) => self.numbers,
+        }
+    }
+
+    fn length(&self, min: Option<usize>, max: Option<usize>, rng: &mut dyn rand::RngCore) -> usize {
+        let min = min.unwrap_or(0);
+        let max = max.unwrap_or(min.max(self.max_length));
+        rng.gen_range(min..=max.max(min))
+    }
+}
+
+fn parse_schema(schema: &Value) -> Result<Schema, String> {
+    let object = match schema {
+        Value::Object(object) => object,
+        Value::Bool(true) => return Ok(Schema::Any),
+        _ => return Err(format!("unsupported schema {schema}")),
+    };
+
+    if let Some(value) = object.get("const") {
+        return Ok(Schema::Enum(vec![value.clone()]));
+    }
+
+    if let Some(values) = object.get("enum") {
+        return match values.as_array() {
+            Some(values) if !values.is_empty() => Ok(Schema::Enum(values.clone())),
+            _ => Err(format!("`enum` must be a non-empty array, not {values}")),
+        };
+    }
+
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(schemas) = object.get(keyword) {
+            return match schemas.as_array() {
+                Some(schemas) if !schemas.is_empty() => Ok(Schema::OneOf(
+                    schemas.iter().map(parse_schema).collect::<Result<_, _>>()?,
+                )),
+                _ => Err(format!(
+                    "`{keyword}` must be a non-empty array, not {schemas}"
+                )),
+            };
+        }
+    }
+
+    let number = |keyword: &str| object.get(keyword).and_then(Value::as_f64);
+    let length = |keyword: &str| {
+        object
+            .get(keyword)
+            .and_then(Value::as_u64)
+            .map(|n| n as usize)
+    };
+
+    // bounds which no value can satisfy are errors, rather than being
+    // silently ignored when generating
+    let range = |min: &str, max: &str| match (number(min), number(max)) {
+        (Some(low), Some(high)) if low > high => {
+            Err(format!("`{min}` {low} is greater than `{max}` {high}"))
+        }
+        bounds => Ok(bounds),
+    };
+
+    let lengths = |min: &str, max: &str| match (length(min), length(max)) {
+        (Some(low), Some(high)) if low > high => {
+            Err(format!("`{min}` {low} is greater than `{max}` {high}"))
+        }
+        bounds => Ok(bounds),
+    };
+
+    let types: Vec<&str> = match object.get("type") {
+        Some(Value::String(t)) => vec![t],
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+        Some(other) => return Err(format!("unsupported type {other}")),
+        None if object.contains_key("properties") => vec!["object"],
+        None if object.contains_key("items") => vec!["array"],
+        None => return Ok(Schema::Any),
+    };
+
+    let mut schemas = types
+        .into_iter()
+        .map(|t| {
+            Ok(match t {
+                "null" => Schema::Null,
+                "boolean" => Schema::Boolean,
+                "integer" => {
+                    let (min, max) = range("minimum", "maximum")?;
+                    if let (Some(low), Some(high)) = (min, max) {
+                        if low.ceil() > high.floor() {
+                            return Err(format!("no integers between {low} and {high}"));
+                        }
+                    }
+
+                    Schema::Integer { min, max }
+                }
+                "number" => {
+                    let (min, max) = range("minimum", "maximum")?;
+                    Schema::Number { min, max }
+                }
+                "string" => {
+                    let (min_length, max_length) = lengths("minLength", "maxLength")?;
+                    Schema::String {
+                        min_length,
+                        max_length,
+                    }
+                }
+                "array" => {
+                    let (min_items, max_items) = lengths("minItems", "maxItems")?;
+                    Schema::Array {
+                        items: Box::new(match object.get("items") {
+                            Some(items) => parse_schema(items)?,
+                            None => Schema::Any,
+                        }),
+                        min_items,
+                        max_items,
+                    }
+                }
+                "object" => {
+                    let mut properties: Vec<(String, Schema)> =
+                        match object.get("properties").and_then(Value::as_object) {
+                            Some(properties) => properties
+                                .iter()
+                                .map(|(name, schema)| Ok((name.clone(), parse_schema(schema)?)))
+                                .collect::<Result<_, String>>()?,
+                            None => Vec::new(),
+                        };
+
+                    let required: Vec<String> =
+                        match object.get("required").and_then(Value::as_array) {
+                            Some(required) => required
+                                .iter()
+                                .filter_map(Value::as_str)
+                                .map(str::to_string)
+                                .collect(),
+                            None => Vec::new(),
+                        };
+
+                    // required properties without a schema can have any value
+                    for name in &required {
+                        if !properties.iter().any(|(n, _)| n == name) {
+                            properties.push((name.clone(), Schema::Any));
+                        }
+                    }
+
+                    Schema::Object {
+                        properties,
+                        required,
+                    }
+                }
+                other => return Err(format!("unsupported type {other:?}")),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(match schemas.len() {
+        0 => return Err("`type` must not be an empty array".to_string()),
+        1 => schemas.remove(0),
+        _ => Schema::OneOf(schemas),
+    })
+}
+
+fn conforms(schema: &Schema, value: &Value) -> bool {
+    let within = |n: f64, min: &Option<f64>, max: &Option<f64>| {
+        min.is_none_or(|min| n >= min) && max.is_none_or(|max| n <= max)
+    };
+
+    let length = |n: usize, min: &Option<usize>, max: &Option<usize>| {
+        min.is_none_or(|min| n >= min) && max.is_none_or(|max| n <= max)
+    };
+
+    match (schema, value) {
+        (Schema::Any, _) | (Schema::Null, Value::Null) | (Schema::Boolean, Value::Bool(_)) => true,
+        (Schema::Integer { min, max }, Value::Number(n)) => {
+            n.is_i64() && within(n.as_f64().unwrap_or_default(), min, max)
+        }
+        (Schema::Number { min, max }, Value::Number(n)) => {
+            within(n.as_f64().unwrap_or_default(), min, max)
+        }
+        (
+            Schema::String {
+                min_length,
+                max_length,
+            },
+            Value::String(s),
+        ) => length(s.chars().count(), min_length, max_length),
+        (
+            Schema::Array {
+                items,
+                min_items,
+                max_items,
+            },
+            Value::Array(values),
+        ) => {
+            length(values.len(), min_items, max_items) && values.iter().all(|v| conforms(items, v))
+        }
+        (
+            Schema::Object {
+                properties,
+                required,
+            },
+            Value::Object(object),
+        ) => {
+            required.iter().all(|name| object.contains_key(name))
+                && object.iter().all(|(name, value)| {
+                    properties
+                        .iter()
+                        .any(|(n, schema)| n == name && conforms(schema, value))
+                })
+        }
+        (Schema::Enum(values), value) => values.contains(value),
+        (Schema::OneOf(schemas), value) => schemas.iter().any(|schema| conforms(schema, value)),
+        _ => false,
+    }
+}
+
+/// The values which are one step simpler than `value`,
+/// and which still conform to `schema`.
+fn candidates(schema: &Schema, value: &Value) -> Vec<Value> {
+    let mut result = Vec::new();
+    match schema {
+        Schema::Enum(values) => {
+            let ix = values.iter().position(|v| v == value).unwrap_or(0);
+            return values[..ix].to_vec();
+        }
+        Schema::OneOf(schemas) => {
+            for schema in schemas.iter().filter(|schema| conforms(schema, value)) {
+                result.extend(candidates(schema, value));
+            }
+
+            return result;
+        }
+        Schema::Any if !value.is_null() => result.push(Value::Null),
+        _ => {}
+    }
+
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => {
+            if *b {
+                result.push(Value::Bool(false));
+            }
+        }
+        Value::Number(n) => {
+            // numbers shrink towards the value nearest 0 within the bounds
+            let (min, max) = match schema {
+                Schema::Integer { min, max } | Schema::Number { min, max } => (*min, *max),
+                _ => (None, None),
+            };
+
+            if let Some(i) = n.as_i64() {
+                let target = 0
+                    .max(min.map_or(i128::MIN, |min| min.ceil() as i128))
+                    .min(max.map_or(i128::MAX, |max| max.floor() as i128));
+                let i = i as i128;
+                let distance = i - target;
+                let mut candidates = vec![target];
+                let mut step = distance / 2;
+                while step != 0 {
+                    candidates.push(i - step);
+                    step /= 2;
+                }
+
+                candidates.push(i - distance.signum());
+                candidates.retain(|c| (c - target).abs() < distance.abs());
+                result.extend(
+                    candidates
+                        .into_iter()
+                        .filter_map(|c| i64::try_from(c).ok())
+                        .map(Value::from),
+                );
+            } else if let Some(f) = n.as_f64() {
+                let target = 0f64
+                    .max(min.unwrap_or(f64::MIN))
+                    .min(max.unwrap_or(f64::MAX));
+                let distance = f - target;
+                let mut candidates = vec![target, f.trunc(), target + distance / 2.0];
+                let mut step = distance / 2.0;
+                while f - step != f {
+                    candidates.push(f - step);
+                    step /= 2.0;
+                }
+
+                candidates.retain(|c| (c - target).abs() < distance.abs());
+                result.extend(
+                    candidates
+                        .into_iter()
+                        .filter_map(Number::from_f64)
+                        .map(Value::Number),
+                );
+            }
+        }
+        Value::String(s) => {
+            if !s.is_empty() {
+                result.push(Value::from(""));
+                let mut chars: Vec<char> = s.chars().collect();
+                chars.pop();
+                result.push(Value::from(chars.iter().collect::<String>()));
+                result.push(Value::from(s.chars().skip(1).collect::<String>()));
+            }
+
+            if let Some(ix) = s.chars().position(|c| c != 'a') {
+                let simpler = s
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| if i == ix { 'a' } else { c })
+                    .collect::<String>();
+                result.push(Value::from(simpler));
+            }
+        }
+        Value::Array(items) => {
+            let item_schema = match schema {
+                Schema::Array { items, .. } => items,
+                _ => &Schema::Any,
+            };
+
+            for ix in (0..items.len()).rev() {
+                let mut fewer = items.clone();
+                fewer.remove(ix);
+                result.push(Value::Array(fewer));
+            }
+
+            if let Schema::Any = schema {
+                result.extend(items.iter().cloned());
+            }
+
+            for (ix, item) in items.iter().enumerate() {
+                for candidate in candidates(item_schema, item) {
+                    let mut items = items.clone();
+                    items[ix] = candidate;
+                    result.push(Value::Array(items));
+                }
+            }
+        }
+        Value::Object(object) => {
+            for key in object.keys().rev() {
+                let mut fewer = object.clone();
+                fewer.remove(key);
+                result.push(Value::Object(fewer));
+            }
+
+            if let Schema::Any = schema {
+                result.extend(object.values().cloned());
+            }
+
+            for (key, value) in object {
+                let property_schema = match schema {
+                    Schema::Object { properties, .. } => properties
+                        .iter()
+                        .find(|(name, _)| name == key)
+                        .map_or(&Schema::Any, |(_, schema)| schema),
+                    _ => &Schema::Any,
+                };
+
+                for candidate in candidates(property_schema, value) {
+                    let mut object = object.clone();
+                    object.insert(key.clone(), candidate);
+                    result.push(Value::Object(object));
+                }
+            }
+        }
+    }
+
+    result.retain(|candidate| conforms(schema, candidate));
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, generators::sample};
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "minimum": 1, "maximum": 100 },
+                "kind": { "enum": ["user", "admin"] },
+                "tags": {
+                    "type": "array",
+                    "items": { "type": "string", "minLength": 1 },
+                    "maxItems": 3,
+                },
+            },
+            "required": ["id", "kind"],
+        })
+    }
+
+    #[test]
+    pub fn generates_conforming_values() {
+        let json = Json::schema(&schema()).unwrap();
+        for value in sample(json.generator(), 1000, 1234) {
+            assert!(conforms(&json.schema, &value), "{value} does not conform");
+            let id = value["id"].as_i64().unwrap();
+            assert!((1..=100).contains(&id));
+        }
+    }
+
+    #[test]
+    pub fn shrinks_within_schema() {
+        let json = Json::schema(&schema()).unwrap();
+        let failure = check(|maat| {
+            let value = maat.generate("value", json.generator());
+            value["tags"].as_array().is_none_or(|tags| tags.len() < 2)
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            failure.shrunk[0].downcast_ref::<Value>().unwrap(),
+            &json!({ "id": 1, "kind": "user", "tags": ["a", "a"] })
+        );
+    }
+
+    #[test]
+    pub fn shrinks_unconstrained_values() {
+        let json = Json::new().keys(["a", "b"]);
+        let failure = check(|maat| {
+            let value = maat.generate("value", json.generator());
+            value.get("b").is_none()
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            failure.shrunk[0].downcast_ref::<Value>().unwrap(),
+            &json!({ "b": null })
+        );
+    }
+
+    #[test]
+    pub fn rejects_unsatisfiable_bounds() {
+        let schema = |schema| Json::schema(&schema).err();
+        assert_eq!(
+            schema(json!({ "type": "number", "minimum": 2, "maximum": 1 })).unwrap(),
+            "`minimum` 2 is greater than `maximum` 1"
+        );
+        assert!(schema(json!({ "type": "integer", "minimum": 1.2, "maximum": 1.8 })).is_some());
+        assert!(schema(json!({ "type": "array", "minItems": 3, "maxItems": 2 })).is_some());
+        assert!(schema(json!({ "type": [] })).is_some());
+    }
+
+    #[test]
+    pub fn generates_numbers_from_an_empty_range() {
+        let json = Json::new().numbers(1.0, -1.0);
+        for value in sample(json.generator(), 100, 1234) {
+            assert!(value.as_f64().is_none_or(|n| n == 1.0), "{value} is not 1");
+        }
+    }
+
+    #[test]
+    pub fn shrinks_bounded_numbers_to_the_boundary() {
+        let shrunk = |schema: Value, fails: fn(f64) -> bool| {
+            let json = Json::schema(&schema).unwrap();
+            let failure = check(|maat| {
+                let value = maat.generate("value", json.generator());
+                !fails(value.as_f64().unwrap())
+            })
+            .unwrap_err();
+
+            failure.shrunk[0].downcast_ref::<Value>().unwrap().clone()
+        };
+
+        let integer = json!({ "type": "integer", "minimum": 1000000, "maximum": 2000000 });
+        assert_eq!(shrunk(integer, |n| n >= 1000100.0), json!(1000100));
+
+        let number = json!({ "type": "number", "minimum": 500.5, "maximum": 1000 });
+        assert_eq!(shrunk(number, |n| n >= 600.0), json!(600.0));
+    }
+
+    #[test]
+    pub fn generates_required_properties_without_schema() {
+        let json = Json::schema(&json!({
+            "type": "object",
+            "properties": { "id": { "type": "integer" } },
+            "required": ["id", "name"],
+        }))
+        .unwrap();
+
+        for value in sample(json.generator(), 100, 1234) {
+            assert!(value.get("name").is_some(), "{value} has no name");
+            assert!(conforms(&json.schema, &value), "{value} does not conform");
+        }
+    }
+}
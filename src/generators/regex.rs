@@ -0,0 +1,302 @@
+//! Generating strings which match a regex.
+//!
+//! A value is generated as a derivation of the regex: which alternative was
+//! taken, how many times each repetition was repeated, and which character
+//! was chosen from each class. Shrinking edits the derivation rather than
+//! the string, so every candidate still matches.
+
+use std::rc::Rc;
+
+use rand::Rng;
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+use crate::{generators::shrink_derivation, Generator, Shrinkable};
+
+/// The number of extra repetitions generated for an unbounded
+/// repetition such as `*` or `+`.
+const MAX_EXTRA_REPETITIONS: u32 = 8;
+
+/// Characters preferred when shrinking, if the class contains them.
+const SIMPLE_CHARS: [char; 3] = ['a', '0', ' '];
+
+/// The code points reserved for surrogates, which are not characters.
+const SURROGATES: (u32, u32) = (0xD800, 0xDFFF);
+
+/// The `string_matching` generator generates strings which match `regex`
+/// in their entirety.
+///
+/// Classes, repetition, alternation and groups are supported. Anchors are
+/// ignored, since the whole string always matches; other look-around
+/// assertions such as `\b` are also ignored, so may not hold.
+///
+/// Shrinks towards fewer repetitions, earlier alternatives and simpler
+/// characters.
+///
+/// # Panics
+/// If `regex` is not a valid regex.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::string_matching, property};
+/// # property(|maat| {
+/// let email = maat.generate("email", string_matching("[a-z]+@[a-z]+\\.(com|org)"));
+/// # true
+/// # });
+/// ```
+pub fn string_matching(regex: &str) -> impl Generator<String> {
+    struct G {
+        hir: Rc<Hir>,
+    }
+
+    let hir = regex_syntax::Parser::new()
+        .parse(regex)
+        .unwrap_or_else(|e| panic!("[maat] Invalid regex {regex:?}: {e}"));
+
+    return G { hir: Rc::new(hir) };
+
+    impl Generator<String> for G {
+        fn generate(&self, rng: &mut dyn rand::RngCore) -> String {
+            render(&self.hir, &derive(&self.hir, rng))
+        }
+
+        fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<String> {
+            let node = derive(&self.hir, rng);
+            let value = render(&self.hir, &node);
+            let (hir, render_hir) = (self.hir.clone(), self.hir.clone());
+            Shrinkable {
+                shrink: shrink_derivation(
+                    node,
+                    move |node| candidates(&hir, node),
+                    move |node| render(&render_hir, node),
+                ),
+                value,
+            }
+        }
+    }
+}
+
+/// A derivation of a string from a [Hir].
+#[derive(Clone, Debug)]
+enum Node {
+    /// For literals, empty strings and assertions.
+    Fixed,
+    /// The character chosen from a class.
+    Char(char),
+    /// The alternative taken.
+    Alternative(usize, Box<Node>),
+    /// The items of a concatenation or repetition.
+    Sequence(Vec<Node>),
+}
+
+fn derive(hir: &Hir, rng: &mut dyn rand::RngCore) -> Node {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Literal(_) | HirKind::Look(_) => Node::Fixed,
+        HirKind::Class(class) => {
+            let ranges = class_ranges(class);
+            let total: u32 = ranges.iter().map(|(start, end)| end - start + 1).sum();
+            if total == 0 {
+                panic!("[maat] Regex class {class:?} cannot match any character");
+            }
+
+            let mut ix = rng.gen_range(0..total);
+            for (start, end) in ranges {
+                if ix <= end - start {
+                    return Node::Char(char::from_u32(start + ix).unwrap());
+                }
+
+                ix -= end - start + 1;
+            }
+
+            unreachable!()
+        }
+        HirKind::Repetition(repetition) => {
+            let max = repetition
+                .max
+                .unwrap_or(repetition.min + MAX_EXTRA_REPETITIONS);
+            let count = rng.gen_range(repetition.min..=max);
+            Node::Sequence((0..count).map(|_| derive(&repetition.sub, rng)).collect())
+        }
+        HirKind::Capture(capture) => derive(&capture.sub, rng),
+        HirKind::Concat(items) => Node::Sequence(items.iter().map(|h| derive(h, rng)).collect()),
+        HirKind::Alternation(alternatives) => {
+            let ix = rng.gen_range(0..alternatives.len());
+            Node::Alternative(ix, Box::new(derive(&alternatives[ix], rng)))
+        }
+    }
+}
+
+/// The simplest derivation of a string from a [Hir].
+fn minimal(hir: &Hir) -> Node {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Literal(_) | HirKind::Look(_) => Node::Fixed,
+        HirKind::Class(class) => Node::Char(simplest_char(class)),
+        HirKind::Repetition(repetition) => Node::Sequence(
+            (0..repetition.min)
+                .map(|_| minimal(&repetition.sub))
+                .collect(),
+        ),
+        HirKind::Capture(capture) => minimal(&capture.sub),
+        HirKind::Concat(items) => Node::Sequence(items.iter().map(minimal).collect()),
+        HirKind::Alternation(alternatives) => {
+            Node::Alternative(0, Box::new(minimal(&alternatives[0])))
+        }
+    }
+}
+
+/// The derivations which are one step simpler than `node`.
+fn candidates(hir: &Hir, node: &Node) -> Vec<Node> {
+    let mut result = Vec::new();
+    match (hir.kind(), node) {
+        (HirKind::Class(class), Node::Char(c)) => {
+            let simplest = simplest_char(class);
+            if *c != simplest {
+                result.push(Node::Char(simplest));
+            }
+        }
+        (HirKind::Repetition(repetition), Node::Sequence(items)) => {
+            let min = repetition.min as usize;
+            if items.len() > min {
+                result.push(Node::Sequence(items[..min].to_vec()));
+                for ix in (0..items.len()).rev() {
+                    let mut fewer = items.clone();
+                    fewer.remove(ix);
+                    result.push(Node::Sequence(fewer));
+                }
+            }
+
+            result.extend(item_candidates(|_| &repetition.sub, items));
+        }
+        (HirKind::Capture(capture), node) => result = candidates(&capture.sub, node),
+        (HirKind::Concat(hirs), Node::Sequence(items)) => {
+            result.extend(item_candidates(|ix| &hirs[ix], items));
+        }
+        (HirKind::Alternation(alternatives), Node::Alternative(ix, inner)) => {
+            for (earlier, alternative) in alternatives[..*ix].iter().enumerate() {
+                result.push(Node::Alternative(earlier, Box::new(minimal(alternative))));
+            }
+
+            for candidate in candidates(&alternatives[*ix], inner) {
+                result.push(Node::Alternative(*ix, Box::new(candidate)));
+            }
+        }
+        _ => {}
+    }
+
+    result
+}
+
+/// The candidates made by simplifying one of `items`.
+fn item_candidates<'h>(hir: impl Fn(usize) -> &'h Hir, items: &[Node]) -> Vec<Node> {
+    let mut result = Vec::new();
+    for (ix, item) in items.iter().enumerate() {
+        for candidate in candidates(hir(ix), item) {
+            let mut items = items.to_vec();
+            items[ix] = candidate;
+            result.push(Node::Sequence(items));
+        }
+    }
+
+    result
+}
+
+fn render(hir: &Hir, node: &Node) -> String {
+    let mut result = String::new();
+    render_into(hir, node, &mut result);
+    result
+}
+
+fn render_into(hir: &Hir, node: &Node, result: &mut String) {
+    match (hir.kind(), node) {
+        (HirKind::Literal(literal), _) => result.push_str(&String::from_utf8_lossy(&literal.0)),
+        (HirKind::Class(_), Node::Char(c)) => result.push(*c),
+        (HirKind::Repetition(repetition), Node::Sequence(items)) => {
+            for item in items {
+                render_into(&repetition.sub, item, result);
+            }
+        }
+        (HirKind::Capture(capture), node) => render_into(&capture.sub, node, result),
+        (HirKind::Concat(hirs), Node::Sequence(items)) => {
+            for (hir, item) in hirs.iter().zip(items) {
+                render_into(hir, item, result);
+            }
+        }
+        (HirKind::Alternation(alternatives), Node::Alternative(ix, inner)) => {
+            render_into(&alternatives[*ix], inner, result);
+        }
+        _ => {}
+    }
+}
+
+/// The inclusive ranges of the class, as code points.
+/// Ranges which span the surrogates are split around them.
+fn class_ranges(class: &Class) -> Vec<(u32, u32)> {
+    match class {
+        Class::Unicode(class) => class
+            .ranges()
+            .iter()
+            .flat_map(|r| {
+                let (start, end) = (r.start() as u32, r.end() as u32);
+                match start < SURROGATES.0 && end > SURROGATES.1 {
+                    true => vec![(start, SURROGATES.0 - 1), (SURROGATES.1 + 1, end)],
+                    false => vec![(start, end)],
+                }
+            })
+            .collect(),
+        Class::Bytes(class) => class
+            .ranges()
+            .iter()
+            .map(|r| (r.start() as u32, r.end() as u32))
+            .collect(),
+    }
+}
+
+fn simplest_char(class: &Class) -> char {
+    let ranges = class_ranges(class);
+    let contains = |c: char| {
+        ranges
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&(c as u32)))
+    };
+
+    SIMPLE_CHARS
+        .into_iter()
+        .find(|c| contains(*c))
+        .or_else(|| ranges.first().and_then(|(start, _)| char::from_u32(*start)))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, generators::sample};
+
+    #[test]
+    pub fn generates_matching_strings() {
+        let pattern = r"^[a-z]{3,8}@([a-z0-9]+\.)+(com|org)$";
+        let regex = ::regex::Regex::new(pattern).unwrap();
+        for value in sample(string_matching(pattern), 1000, 1234) {
+            assert!(regex.is_match(&value), "{value:?} does not match");
+        }
+    }
+
+    #[test]
+    pub fn generates_from_classes_spanning_surrogates() {
+        for pattern in [".", "[^a]", r"[\u{D000}-\u{E000}]"] {
+            let regex = ::regex::Regex::new(&format!("^{pattern}$")).unwrap();
+            for value in sample(string_matching(pattern), 1000, 1234) {
+                assert!(regex.is_match(&value), "{value:?} does not match {pattern}");
+            }
+        }
+    }
+
+    #[test]
+    pub fn shrinks_to_simplest_match() {
+        let failure = check(|maat| {
+            let s = maat.generate("s", string_matching("(foo|[a-z]+)x"));
+            s.len() < 5
+        })
+        .unwrap_err();
+
+        assert_eq!(failure.shrunk[0].downcast_ref::<String>().unwrap(), "aaaax");
+    }
+}
@@ -0,0 +1,317 @@
+//! Generating rearrangements of given sequences: permutations,
+//! subsequences and interleavings.
+//!
+//! Each value is generated as a list of indices into the given items, and
+//! shrinking simplifies the indices (towards the original order, or fewer
+//! of them) rather than the items themselves.
+
+use std::rc::Rc;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{generators::shrink_derivation, Generator, Shrinkable, Shrinker};
+
+/// The `permutation` generator generates the `items` in a random order.
+///
+/// Shrinks towards the original order.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::permutation, property};
+/// # property(|maat| {
+/// let jobs = maat.generate("jobs", permutation(vec!["build", "test", "deploy"]));
+/// # true
+/// # });
+/// ```
+pub fn permutation<T: Clone + 'static>(items: Vec<T>) -> impl Generator<Vec<T>> {
+    struct G<T> {
+        items: Rc<Vec<T>>,
+    }
+
+    return G {
+        items: Rc::new(items),
+    };
+
+    impl<T: Clone + 'static> Generator<Vec<T>> for G<T> {
+        fn generate(&self, rng: &mut dyn rand::RngCore) -> Vec<T> {
+            let mut items = self.items.to_vec();
+            items.shuffle(rng);
+            items
+        }
+
+        fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<Vec<T>> {
+            let mut order: Vec<usize> = (0..self.items.len()).collect();
+            order.shuffle(rng);
+            let items = self.items.clone();
+            Shrinkable {
+                value: select(&items, &order),
+                shrink: shrink_indices(order, permutation_candidates, move |order| {
+                    select(&items, order)
+                }),
+            }
+        }
+    }
+}
+
+/// The `subsequence` generator generates a subset of the `items`,
+/// keeping their original order.
+///
+/// Shrinks towards fewer items.
+pub fn subsequence<T: Clone + 'static>(items: Vec<T>) -> impl Generator<Vec<T>> {
+    struct G<T> {
+        items: Rc<Vec<T>>,
+    }
+
+    return G {
+        items: Rc::new(items),
+    };
+
+    impl<T: Clone + 'static> Generator<Vec<T>> for G<T> {
+        fn generate(&self, rng: &mut dyn rand::RngCore) -> Vec<T> {
+            self.items
+                .iter()
+                .filter(|_| rng.gen_bool(0.5))
+                .cloned()
+                .collect()
+        }
+
+        fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<Vec<T>> {
+            let selected: Vec<usize> = (0..self.items.len())
+                .filter(|_| rng.gen_bool(0.5))
+                .collect();
+            let items = self.items.clone();
+            Shrinkable {
+                value: select(&items, &selected),
+                shrink: shrink_indices(selected, subsequence_candidates, move |selected| {
+                    select(&items, selected)
+                }),
+            }
+        }
+    }
+}
+
+/// The `interleaving` generator generates all the items of the `sequences`,
+/// merged in a random order which keeps the order within each sequence.
+/// Every interleaving is equally likely.
+///
+/// Shrinks towards the sequences one after another.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::interleaving, property};
+/// # property(|maat| {
+/// # let (client_a_ops, client_b_ops) = (vec!["a1", "a2"], vec!["b1"]);
+/// // the operations of two clients, as seen by the server
+/// let ops = maat.generate("ops", interleaving(vec![client_a_ops, client_b_ops]));
+/// # true
+/// # });
+/// ```
+pub fn interleaving<T: Clone + 'static>(sequences: Vec<Vec<T>>) -> impl Generator<Vec<T>> {
+    struct G<T> {
+        sequences: Rc<Vec<Vec<T>>>,
+    }
+
+    return G {
+        sequences: Rc::new(sequences),
+    };
+
+    impl<T: Clone + 'static> Generator<Vec<T>> for G<T> {
+        fn generate(&self, rng: &mut dyn rand::RngCore) -> Vec<T> {
+            merge(&self.sequences, &interleave(&self.sequences, rng))
+        }
+
+        fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<Vec<T>> {
+            let sources = interleave(&self.sequences, rng);
+            let sequences = self.sequences.clone();
+            Shrinkable {
+                value: merge(&sequences, &sources),
+                shrink: shrink_indices(sources, interleaving_candidates, move |sources| {
+                    merge(&sequences, sources)
+                }),
+            }
+        }
+    }
+}
+
+/// A shrinker for values which are rendered from a list of indices.
+fn shrink_indices<T: 'static>(
+    indices: Vec<usize>,
+    candidates: fn(&[usize]) -> Vec<Vec<usize>>,
+    render: impl Fn(&[usize]) -> T + 'static,
+) -> Shrinker<T> {
+    shrink_derivation(
+        indices,
+        move |indices: &Vec<usize>| candidates(indices),
+        move |indices: &Vec<usize>| render(indices),
+    )
+}
+
+fn select<T: Clone>(items: &[T], indices: &[usize]) -> Vec<T> {
+    indices.iter().map(|&ix| items[ix].clone()).collect()
+}
+
+/// Every candidate has fewer inversions (pairs which are out of order)
+/// than `order`, so shrinking always reaches the original order.
+fn permutation_candidates(order: &[usize]) -> Vec<Vec<usize>> {
+    let mut result = Vec::new();
+    if order.windows(2).any(|pair| pair[0] > pair[1]) {
+        result.push((0..order.len()).collect());
+    }
+
+    let mut swapped = |i: usize, j: usize| {
+        let mut order = order.to_vec();
+        order.swap(i, j);
+        result.push(order);
+    };
+
+    // move an item forward to its original position
+    for (ix, &item) in order.iter().enumerate() {
+        let position = order.iter().position(|&other| other == ix).unwrap();
+        if item > ix && position > ix {
+            swapped(ix, position);
+        }
+    }
+
+    for i in 0..order.len() {
+        for j in i + 1..order.len() {
+            if order[i] > order[j] {
+                swapped(i, j);
+            }
+        }
+    }
+
+    result
+}
+
+fn subsequence_candidates(selected: &[usize]) -> Vec<Vec<usize>> {
+    let mut result = Vec::new();
+    if selected.len() > 1 {
+        let half = selected.len() / 2;
+        result.push(Vec::new());
+        result.push(selected[..half].to_vec());
+        result.push(selected[half..].to_vec());
+    }
+
+    for ix in 0..selected.len() {
+        let mut fewer = selected.to_vec();
+        fewer.remove(ix);
+        result.push(fewer);
+    }
+
+    result
+}
+
+/// The source of each item of an interleaving, chosen so
+/// that every interleaving is equally likely.
+fn interleave<T>(sequences: &[Vec<T>], rng: &mut dyn rand::RngCore) -> Vec<usize> {
+    let mut remaining: Vec<usize> = sequences.iter().map(Vec::len).collect();
+    let total: usize = remaining.iter().sum();
+    let mut sources = Vec::with_capacity(total);
+    for left in (1..=total).rev() {
+        let mut choice = rng.gen_range(0..left);
+        let source = remaining
+            .iter()
+            .position(|&count| {
+                if choice < count {
+                    return true;
+                }
+
+                choice -= count;
+                false
+            })
+            .unwrap();
+
+        remaining[source] -= 1;
+        sources.push(source);
+    }
+
+    sources
+}
+
+fn merge<T: Clone>(sequences: &[Vec<T>], sources: &[usize]) -> Vec<T> {
+    let mut positions = vec![0; sequences.len()];
+    sources
+        .iter()
+        .map(|&source| {
+            positions[source] += 1;
+            sequences[source][positions[source] - 1].clone()
+        })
+        .collect()
+}
+
+/// Like [permutation_candidates], but the order within each sequence
+/// is kept, so only adjacent items from different sequences are swapped.
+fn interleaving_candidates(sources: &[usize]) -> Vec<Vec<usize>> {
+    let mut result = Vec::new();
+    if sources.windows(2).any(|pair| pair[0] > pair[1]) {
+        let mut sorted = sources.to_vec();
+        sorted.sort();
+        result.push(sorted);
+    }
+
+    for ix in 1..sources.len() {
+        if sources[ix - 1] > sources[ix] {
+            let mut sources = sources.to_vec();
+            sources.swap(ix - 1, ix);
+            result.push(sources);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, generators::sample};
+
+    #[test]
+    pub fn shrinks_towards_original_order() {
+        let failure = check(|maat| {
+            let order = maat.generate("order", permutation((0..8).collect()));
+            order[0] == 0
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            failure.shrunk[0].downcast_ref::<Vec<i32>>().unwrap(),
+            &[1, 0, 2, 3, 4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    pub fn shrinks_to_smaller_subsequence() {
+        let failure = check(|maat| {
+            let items = maat.generate("items", subsequence((0..10).collect()));
+            !(items.contains(&3) && items.contains(&7))
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            failure.shrunk[0].downcast_ref::<Vec<i32>>().unwrap(),
+            &[3, 7]
+        );
+    }
+
+    #[test]
+    pub fn interleavings_keep_sequence_order() {
+        let sequences = vec![vec![1, 2, 3], vec![10, 20]];
+        for merged in sample(interleaving(sequences.clone()), 100, 1234) {
+            let ones: Vec<_> = merged.iter().filter(|&&x| x < 10).collect();
+            assert_eq!(ones, [&1, &2, &3]);
+            assert_eq!(merged.len(), 5);
+        }
+
+        let failure = check(|maat| {
+            let merged = maat.generate("merged", interleaving(sequences.clone()));
+            let position = |x| merged.iter().position(|&y| y == x).unwrap();
+            position(3) < position(10)
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            failure.shrunk[0].downcast_ref::<Vec<i32>>().unwrap(),
+            &[1, 2, 10, 3, 20]
+        );
+    }
+}
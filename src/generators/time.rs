@@ -0,0 +1,458 @@
+//! Generating durations, times and calendar dates.
+//!
+//! Each kind of value is generated as an integer (nanoseconds or days),
+//! biased towards values where time-handling code tends to go wrong: the
+//! epoch, leap days, the ends of months and years, and the limits of the
+//! types involved. Shrinking moves towards zero, or towards the epoch.
+
+use std::{
+    fmt::Display,
+    ops::{Bound, RangeBounds},
+    rc::Rc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{generators::shrink_candidates, Generator, Shrinkable};
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+const NANOS_PER_DAY: i128 = 86_400 * NANOS_PER_SEC;
+
+/// The days since the epoch of 0001-01-01 and 9999-12-31,
+/// which are used when the range of dates or times is unbounded.
+const MIN_DAYS: i128 = -719_162;
+const MAX_DAYS: i128 = 2_932_896;
+
+/// The days since the epoch of 1601-01-01, which is used instead of
+/// [MIN_DAYS] for unbounded times, since it is the earliest time
+/// that every platform's `SystemTime` can represent.
+const MIN_SYSTEM_DAYS: i128 = -134_774;
+
+/// Durations at which units roll over, or which are
+/// the limits of common representations.
+const DURATION_EDGES: &[i128] = &[
+    0,
+    1,
+    1_000,
+    1_000_000,
+    NANOS_PER_SEC - 1,
+    NANOS_PER_SEC,
+    60 * NANOS_PER_SEC,
+    3_600 * NANOS_PER_SEC,
+    NANOS_PER_DAY,
+    u32::MAX as i128 * 1_000_000,
+    i64::MAX as i128,
+    i64::MAX as i128 * NANOS_PER_SEC,
+    u64::MAX as i128 * NANOS_PER_SEC,
+    u64::MAX as i128 * NANOS_PER_SEC + NANOS_PER_SEC - 1,
+];
+
+/// Times (as nanoseconds since the epoch) at which 32-bit
+/// timestamps overflow.
+const TIME_EDGES: &[i128] = &[
+    -NANOS_PER_SEC,
+    -1,
+    0,
+    1,
+    i32::MAX as i128 * NANOS_PER_SEC,
+    (i32::MAX as i128 + 1) * NANOS_PER_SEC,
+    u32::MAX as i128 * NANOS_PER_SEC,
+    (u32::MAX as i128 + 1) * NANOS_PER_SEC,
+];
+
+/// A calendar date in the proleptic Gregorian calendar.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    /// From 1 to 12.
+    pub month: u32,
+    /// From 1 to the length of the month.
+    pub day: u32,
+}
+
+impl Date {
+    /// # Panics
+    /// If the date does not exist, such as 2023-02-29.
+    pub fn new(year: i32, month: u32, day: u32) -> Date {
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            panic!("[maat] Invalid date {year:04}-{month:02}-{day:02}");
+        }
+
+        Date { year, month, day }
+    }
+
+    /// The date which is `days` after 1970-01-01.
+    pub fn from_days_since_epoch(days: i64) -> Date {
+        // from Howard Hinnant's `civil_from_days`
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Date {
+            year: year as i32,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+
+    /// The number of days after 1970-01-01, which is negative
+    /// for earlier dates.
+    pub fn days_since_epoch(&self) -> i64 {
+        // from Howard Hinnant's `days_from_civil`
+        let (month, day) = (i64::from(self.month), i64::from(self.day));
+        let year = i64::from(self.year) - i64::from(month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// The `duration` generator generates durations within the `bounds`,
+/// which may be unbounded (as in `duration(..)`).
+///
+/// Values are biased towards the bounds, the points at which units roll
+/// over, and the limits of common representations (up to [Duration::MAX]).
+/// Shrinks towards the smallest duration in the bounds.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::duration, property};
+/// # use std::time::Duration;
+/// # property(|maat| {
+/// let timeout = maat.generate("timeout", duration(..Duration::from_secs(60)));
+/// # true
+/// # });
+/// ```
+pub fn duration(bounds: impl RangeBounds<Duration>) -> impl Generator<Duration> {
+    let (min, max) = resolve(
+        bounds,
+        |d| d.as_nanos() as i128,
+        (0, Duration::MAX.as_nanos() as i128),
+    );
+    Scale {
+        min,
+        max,
+        target: min,
+        units: &[NANOS_PER_SEC, 1_000_000],
+        edge: |rng| *DURATION_EDGES.choose(rng).unwrap(),
+    }
+    .generator(nanos_to_duration, |duration| duration.as_nanos() as i128)
+}
+
+/// The `system_time` generator generates times within the `bounds`. Unbounded
+/// times are between the years 1601 and 9999.
+///
+/// Values are biased towards the bounds, the epoch, the times at which
+/// 32-bit timestamps overflow, and the starts and ends of days around leap
+/// days and the ends of months and years. Shrinks towards the epoch, or the
+/// nearest time in the bounds.
+pub fn system_time(bounds: impl RangeBounds<SystemTime>) -> impl Generator<SystemTime> {
+    let (min, max) = resolve(
+        bounds,
+        nanos_since_epoch,
+        (
+            MIN_SYSTEM_DAYS * NANOS_PER_DAY,
+            (MAX_DAYS + 1) * NANOS_PER_DAY - 1,
+        ),
+    );
+
+    Scale {
+        min,
+        max,
+        target: 0.clamp(min, max),
+        units: &[NANOS_PER_DAY, NANOS_PER_SEC, 1_000_000],
+        edge: |rng| match rng.gen() {
+            true => *TIME_EDGES.choose(rng).unwrap(),
+            // the first or last nanosecond of a day
+            false => {
+                let day = calendar_edge(rng) * NANOS_PER_DAY;
+                *[day, day + NANOS_PER_DAY - 1].choose(rng).unwrap()
+            }
+        },
+    }
+    .generator(
+        |nanos| match nanos >= 0 {
+            true => UNIX_EPOCH + nanos_to_duration(nanos),
+            false => UNIX_EPOCH - nanos_to_duration(-nanos),
+        },
+        nanos_since_epoch,
+    )
+}
+
+/// The `date` generator generates dates within the `bounds`. Unbounded
+/// dates are between the years 1 and 9999.
+///
+/// Values are biased towards the bounds, the epoch, leap days (including
+/// the non-leap years 1900 and 2100), and the ends of months and years.
+/// Shrinks towards 1970-01-01, or the nearest date in the bounds.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::{date, Date}, property};
+/// # property(|maat| {
+/// let due = maat.generate("due", date(Date::new(2000, 1, 1)..));
+/// # true
+/// # });
+/// ```
+pub fn date(bounds: impl RangeBounds<Date>) -> impl Generator<Date> {
+    let (min, max) = resolve(
+        bounds,
+        |date| date.days_since_epoch() as i128,
+        (MIN_DAYS, MAX_DAYS),
+    );
+
+    Scale {
+        min,
+        max,
+        target: 0.clamp(min, max),
+        units: &[],
+        edge: calendar_edge,
+    }
+    .generator(
+        |days| Date::from_days_since_epoch(days as i64),
+        |date| date.days_since_epoch() as i128,
+    )
+}
+
+/// The inclusive range of `bounds`, as integers.
+fn resolve<T>(
+    bounds: impl RangeBounds<T>,
+    to_int: impl Fn(&T) -> i128,
+    (default_min, default_max): (i128, i128),
+) -> (i128, i128) {
+    let min = match bounds.start_bound() {
+        Bound::Included(x) => to_int(x),
+        Bound::Excluded(x) => to_int(x) + 1,
+        Bound::Unbounded => default_min,
+    };
+
+    let max = match bounds.end_bound() {
+        Bound::Included(x) => to_int(x),
+        Bound::Excluded(x) => to_int(x) - 1,
+        Bound::Unbounded => default_max,
+    };
+
+    if min > max {
+        panic!("[maat] Empty range of times");
+    }
+
+    (min, max)
+}
+
+/// A range of integers to generate from, biased towards
+/// some edge values and towards the `target`.
+#[derive(Copy, Clone)]
+struct Scale {
+    min: i128,
+    max: i128,
+    /// The value which shrinking moves towards.
+    target: i128,
+    /// Round numbers to shrink to, such as whole seconds.
+    units: &'static [i128],
+    /// Generates an edge value, which may be outside the range.
+    edge: fn(&mut dyn rand::RngCore) -> i128,
+}
+
+impl Scale {
+    fn generator<T: 'static>(
+        self,
+        from_int: fn(i128) -> T,
+        to_int: fn(&T) -> i128,
+    ) -> impl Generator<T> {
+        struct G<T> {
+            scale: Scale,
+            from_int: fn(i128) -> T,
+            to_int: fn(&T) -> i128,
+        }
+
+        return G {
+            scale: self,
+            from_int,
+            to_int,
+        };
+
+        impl<T: 'static> Generator<T> for G<T> {
+            fn generate(&self, rng: &mut dyn rand::RngCore) -> T {
+                (self.from_int)(self.scale.generate(rng))
+            }
+
+            fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<T> {
+                let (scale, from_int, to_int) = (self.scale, self.from_int, self.to_int);
+                Shrinkable {
+                    value: self.generate(rng),
+                    shrink: Rc::new(move |value, is_valid| {
+                        let candidates = |value: &i128| scale.candidates(*value);
+                        let value = to_int(value);
+                        shrink_candidates(&value, candidates, |c| is_valid(from_int(*c))).is_some()
+                    }),
+                }
+            }
+        }
+    }
+
+    fn generate(&self, rng: &mut dyn rand::RngCore) -> i128 {
+        match rng.gen_range(0..4) {
+            0 => {
+                let edge = (self.edge)(rng);
+                match (self.min..=self.max).contains(&edge) {
+                    true => edge,
+                    false => *[self.min, self.max, self.target].choose(rng).unwrap(),
+                }
+            }
+            1 => rng.gen_range(self.min..=self.max),
+            _ => {
+                // a magnitude with a uniformly distributed number of bits
+                let magnitude = (rng.gen::<u128>() >> rng.gen_range(1..128)) as i128;
+                let value = match rng.gen() {
+                    true => self.target.saturating_add(magnitude),
+                    false => self.target.saturating_sub(magnitude),
+                };
+
+                value.clamp(self.min, self.max)
+            }
+        }
+    }
+
+    /// The values which are closer to the target than `value`.
+    fn candidates(&self, value: i128) -> Vec<i128> {
+        let distance = value - self.target;
+        let mut result = vec![self.target];
+        for unit in self.units {
+            result.push(self.target + distance / unit * unit);
+        }
+
+        result.push(self.target + distance / 2);
+        result.push(value - distance.signum());
+        result.retain(|c| (c - self.target).abs() < distance.abs());
+        result.dedup();
+        result
+    }
+}
+
+/// A day (as days since the epoch) at a leap day or
+/// the end of a month or year, in a random year.
+fn calendar_edge(rng: &mut dyn rand::RngCore) -> i128 {
+    let year = match rng.gen() {
+        true => *[1900, 1970, 2000, 2038, 2100].choose(rng).unwrap(),
+        false => rng.gen_range(1..=9999),
+    };
+
+    let date = match rng.gen_range(0..5) {
+        0 => Date::new(year, 1, 1),
+        1 => Date::new(year, 12, 31),
+        2 => Date::new(year, 2, days_in_month(year, 2)),
+        3 => Date::new(year, 3, 1),
+        _ => {
+            let month = rng.gen_range(1..=12);
+            Date::new(year, month, days_in_month(year, month))
+        }
+    };
+
+    date.days_since_epoch() as i128
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn nanos_since_epoch(time: &SystemTime) -> i128 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    }
+}
+
+fn nanos_to_duration(nanos: i128) -> Duration {
+    Duration::new(
+        (nanos / NANOS_PER_SEC) as u64,
+        (nanos % NANOS_PER_SEC) as u32,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, generators::sample};
+
+    #[test]
+    pub fn dates_convert_to_days() {
+        assert_eq!(Date::new(1970, 1, 1).days_since_epoch(), 0);
+        assert_eq!(Date::new(2000, 3, 1).days_since_epoch(), 11_017);
+        assert_eq!(Date::new(1, 1, 1).days_since_epoch(), MIN_DAYS as i64);
+        assert_eq!(Date::new(9999, 12, 31).days_since_epoch(), MAX_DAYS as i64);
+        assert_eq!(
+            Date::new(1601, 1, 1).days_since_epoch(),
+            MIN_SYSTEM_DAYS as i64
+        );
+        for date in sample(date(..), 1000, 1234) {
+            assert_eq!(Date::from_days_since_epoch(date.days_since_epoch()), date);
+        }
+    }
+
+    #[test]
+    pub fn generates_edge_values() {
+        let durations = sample(duration(..), 1000, 1234);
+        assert!(durations.contains(&Duration::ZERO));
+        assert!(durations.contains(&Duration::MAX));
+        assert!(durations.contains(&Duration::from_secs(u64::MAX)));
+
+        let dates = sample(date(..), 1000, 1234);
+        assert!(dates.contains(&Date::new(1970, 1, 1)));
+        assert!(dates.iter().any(|d| d.month == 2 && d.day == 29));
+
+        let bounds = Date::new(2020, 1, 1)..Date::new(2021, 1, 1);
+        assert!(sample(date(bounds.clone()), 1000, 1234)
+            .iter()
+            .all(|d| bounds.contains(d)));
+    }
+
+    #[test]
+    pub fn shrinks_towards_epoch() {
+        let failure = check(|maat| {
+            let time = maat.generate("time", system_time(..));
+            time < UNIX_EPOCH + Duration::from_secs(1_000_000)
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            failure.shrunk[0].downcast_ref::<SystemTime>().unwrap(),
+            &(UNIX_EPOCH + Duration::from_secs(1_000_000))
+        );
+
+        let failure = check(|maat| {
+            let timeout = maat.generate("timeout", duration(Duration::from_secs(1)..));
+            timeout < Duration::from_millis(1500)
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            failure.shrunk[0].downcast_ref::<Duration>().unwrap(),
+            &Duration::from_millis(1500)
+        );
+    }
+}
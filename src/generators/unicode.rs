@@ -0,0 +1,286 @@
+//! Generating strings which exercise Unicode handling.
+//!
+//! A string is generated as a sequence of clusters, each drawn from a
+//! category of characters which commonly cause bugs: combining marks,
+//! right-to-left text, emoji sequences, unusual whitespace, and the code
+//! points at the edges of the surrogate range and the planes. Shrinking
+//! removes clusters and replaces them with ASCII.
+
+use rand::{seq::SliceRandom, Rng};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{generators::shrink_derivation, Generator, Shrinkable};
+
+/// Whitespace and invisible characters, beyond the ASCII space.
+const WHITESPACE: &[char] = &[
+    '\t', '\n', '\r', '\u{0B}', '\u{0C}', '\u{85}', '\u{A0}', '\u{1680}', '\u{2000}', '\u{2003}',
+    '\u{2009}', '\u{200A}', '\u{200B}', '\u{2028}', '\u{2029}', '\u{202F}', '\u{205F}', '\u{3000}',
+    '\u{FEFF}',
+];
+
+/// Bidirectional formatting characters.
+const BIDI_CONTROLS: &[char] = &[
+    '\u{200E}', '\u{200F}', '\u{202A}', '\u{202B}', '\u{202C}', '\u{202D}', '\u{202E}', '\u{2066}',
+    '\u{2067}', '\u{2068}', '\u{2069}',
+];
+
+/// People, which can be joined into families with a zero-width joiner.
+const PEOPLE: &[char] = &['\u{1F468}', '\u{1F469}', '\u{1F466}', '\u{1F467}'];
+
+/// Code points next to the surrogates, the noncharacters,
+/// and the ends of the planes.
+const EDGES: &[char] = &[
+    '\u{0}',
+    '\u{7F}',
+    '\u{80}',
+    '\u{FF}',
+    '\u{D7FF}',
+    '\u{E000}',
+    '\u{FDD0}',
+    '\u{FFFD}',
+    '\u{FFFE}',
+    '\u{FFFF}',
+    '\u{10000}',
+    '\u{1FFFF}',
+    '\u{10FFFF}',
+];
+
+/// Characters which are changed by normalization: precomposed letters (which
+/// are decomposed when generated), and compatibility characters.
+const DECOMPOSABLE: &[char] = &[
+    'é', 'ñ', 'Å', 'ö', 'ệ', '가', 'ﬁ', '\u{2126}', '\u{212B}', '①', 'ｶ', '\u{FB2A}',
+];
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// Options for generating strings which exercise Unicode handling.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::Unicode, property};
+/// # property(|maat| {
+/// let name = maat.generate("name", Unicode::new().max_length(20).generator());
+/// # true
+/// # });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Unicode {
+    min_length: usize,
+    max_length: usize,
+    non_normalized: bool,
+}
+
+impl Default for Unicode {
+    fn default() -> Self {
+        Unicode::new()
+    }
+}
+
+impl Unicode {
+    /// Options for generating strings of up to 10 clusters,
+    /// which are in Normalization Form C.
+    pub fn new() -> Unicode {
+        Unicode {
+            min_length: 0,
+            max_length: 10,
+            non_normalized: false,
+        }
+    }
+
+    /// Sets the minimum number of clusters: a cluster is a single
+    /// character, a character with combining marks, or an emoji sequence.
+    pub fn min_length(self, min_length: usize) -> Self {
+        Unicode { min_length, ..self }
+    }
+
+    /// Sets the maximum number of clusters.
+    pub fn max_length(self, max_length: usize) -> Self {
+        Unicode { max_length, ..self }
+    }
+
+    /// Sets whether strings may be generated which are not normalized,
+    /// such as decomposed letters and compatibility characters.
+    pub fn non_normalized(self, non_normalized: bool) -> Self {
+        Unicode {
+            non_normalized,
+            ..self
+        }
+    }
+
+    pub fn generator(&self) -> impl Generator<String> {
+        struct G {
+            unicode: Unicode,
+        }
+
+        return G {
+            unicode: self.clone(),
+        };
+
+        impl Generator<String> for G {
+            fn generate(&self, rng: &mut dyn rand::RngCore) -> String {
+                self.unicode.render(&self.unicode.clusters(rng))
+            }
+
+            fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<String> {
+                let clusters = self.unicode.clusters(rng);
+                let value = self.unicode.render(&clusters);
+                let (unicode, render) = (self.unicode.clone(), self.unicode.clone());
+                Shrinkable {
+                    shrink: shrink_derivation(
+                        clusters,
+                        move |clusters| unicode.candidates(clusters),
+                        move |clusters| render.render(clusters),
+                    ),
+                    value,
+                }
+            }
+        }
+    }
+
+    fn clusters(&self, rng: &mut dyn rand::RngCore) -> Vec<String> {
+        let length = rng.gen_range(self.min_length..=self.max_length.max(self.min_length));
+        (0..length).map(|_| self.cluster(rng)).collect()
+    }
+
+    fn cluster(&self, rng: &mut dyn rand::RngCore) -> String {
+        let categories = if self.non_normalized { 9 } else { 8 };
+        match rng.gen_range(0..categories) {
+            // ASCII is the most common, so that the other
+            // categories appear next to ordinary text
+            0 | 1 => rng.gen_range(' '..='~').to_string(),
+            2 => WHITESPACE.choose(rng).unwrap().to_string(),
+            3 => {
+                let mut cluster = rng.gen_range('a'..='z').to_string();
+                for _ in 0..rng.gen_range(1..=3) {
+                    cluster.push(rng.gen_range('\u{300}'..='\u{36F}'));
+                }
+
+                cluster
+            }
+            4 => match rng.gen_range(0..3) {
+                0 => rng.gen_range('\u{5D0}'..='\u{5EA}').to_string(),
+                1 => rng.gen_range('\u{627}'..='\u{64A}').to_string(),
+                _ => BIDI_CONTROLS.choose(rng).unwrap().to_string(),
+            },
+            5 => match rng.gen_range(0..3) {
+                0 => {
+                    let people: Vec<String> = (0..rng.gen_range(2..=4))
+                        .map(|_| PEOPLE.choose(rng).unwrap().to_string())
+                        .collect();
+                    people.join(&ZERO_WIDTH_JOINER.to_string())
+                }
+                1 => {
+                    // a skin tone modifier, or a variation selector
+                    let mut cluster = PEOPLE.choose(rng).unwrap().to_string();
+                    cluster.push(match rng.gen() {
+                        true => rng.gen_range('\u{1F3FB}'..='\u{1F3FF}'),
+                        false => '\u{FE0F}',
+                    });
+                    cluster
+                }
+                _ => {
+                    // a flag
+                    let regional_indicators = '\u{1F1E6}'..='\u{1F1FF}';
+                    [
+                        rng.gen_range(regional_indicators.clone()),
+                        rng.gen_range(regional_indicators),
+                    ]
+                    .iter()
+                    .collect()
+                }
+            },
+            6 => EDGES.choose(rng).unwrap().to_string(),
+            7 => rng.gen::<char>().to_string(),
+            _ => {
+                let c = *DECOMPOSABLE.choose(rng).unwrap();
+                match rng.gen() {
+                    true => c.to_string(),
+                    false => c.nfd().collect(),
+                }
+            }
+        }
+    }
+
+    fn render(&self, clusters: &[String]) -> String {
+        let value: String = clusters.concat();
+        match self.non_normalized {
+            true => value,
+            false => value.nfc().collect(),
+        }
+    }
+
+    /// The cluster sequences which are one step simpler than `clusters`.
+    fn candidates(&self, clusters: &[String]) -> Vec<Vec<String>> {
+        let mut result = Vec::new();
+        if clusters.len() > self.min_length {
+            result.push(clusters[..self.min_length].to_vec());
+            for ix in (0..clusters.len()).rev() {
+                let mut fewer = clusters.to_vec();
+                fewer.remove(ix);
+                result.push(fewer);
+            }
+        }
+
+        for (ix, cluster) in clusters.iter().enumerate() {
+            let mut simpler = Vec::new();
+            if !cluster.is_ascii() {
+                simpler.push("a".to_string());
+                // without its combining marks or joined characters, or
+                // only the first of them which isn't ASCII
+                let first = cluster.chars().next();
+                let first_unicode = cluster.chars().find(|c| !c.is_ascii());
+                for c in [first, first_unicode].into_iter().flatten() {
+                    let c = c.to_string();
+                    if c != *cluster && !simpler.contains(&c) {
+                        simpler.push(c);
+                    }
+                }
+            } else if cluster != "a" {
+                simpler.push("a".to_string());
+            }
+
+            for replacement in simpler {
+                let mut clusters = clusters.to_vec();
+                clusters[ix] = replacement;
+                result.push(clusters);
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, generators::sample};
+
+    #[test]
+    pub fn generates_troublesome_characters() {
+        let strings = sample(Unicode::new().generator(), 1000, 1234);
+        assert!(strings.iter().any(|s| s.contains(ZERO_WIDTH_JOINER)));
+        assert!(strings.iter().any(|s| s.contains('\u{FEFF}')));
+        assert!(strings.iter().any(|s| s.chars().any(|c| c > '\u{FFFF}')));
+        assert!(strings.iter().all(|s| s.nfc().eq(s.chars())));
+
+        let strings = sample(Unicode::new().non_normalized(true).generator(), 1000, 1234);
+        assert!(strings.iter().any(|s| !s.nfc().eq(s.chars())));
+    }
+
+    #[test]
+    pub fn shrinks_towards_ascii() {
+        // without normalization, which could combine clusters
+        let unicode = Unicode::new().min_length(3).non_normalized(true);
+        let failure = check(|maat| maat.generate("s", unicode.generator()).is_ascii()).unwrap_err();
+
+        let shrunk = failure.shrunk[0].downcast_ref::<String>().unwrap();
+        let chars: Vec<char> = shrunk.chars().collect();
+        assert_eq!(chars.len(), 3, "{shrunk:?}");
+        assert_eq!(chars.iter().filter(|c| **c == 'a').count(), 2, "{shrunk:?}");
+        assert_eq!(
+            chars.iter().filter(|c| !c.is_ascii()).count(),
+            1,
+            "{shrunk:?}"
+        );
+    }
+}
@@ -28,6 +28,8 @@
 //! x: i64 = 12
 //! y: i64 = 36
 //!
+//!
+//! [maat] Found at iteration 3 with seed 11520737254129440457, after 9 shrink steps
 //! ', src/lib.rs:287:13
 //! ```
 //!
@@ -41,19 +43,51 @@
 //! - Finally, once it has a recording, it tries to shrink the recording by
 //!   re-running the test in [Mode::Shrinking].
 
+// a [Failure] is only returned once per run, so its size doesn't matter
+#![allow(clippy::result_large_err)]
+
+use coverage::Coverage;
 use dynamic::Dynamic;
 use rand::SeedableRng;
 use rand_xoshiro::Xoshiro256PlusPlus as RNG;
 use std::{
     any::type_name,
-    cell::RefCell,
-    fmt::{Debug, Display, Write},
+    cell::{Cell, RefCell},
+    fmt::{Debug, Write},
+    hash::{DefaultHasher, Hash, Hasher},
     ops::DerefMut,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
     rc::Rc,
+    sync::Once,
     time::Instant,
 };
 
+mod choices;
+mod coverage;
+mod executor;
+mod fuzz;
 pub mod generators;
+mod parallel;
+mod properties;
+mod regression;
+mod replay;
+mod report;
+mod search;
+pub mod state_machine;
+
+pub use choices::BufferRng;
+pub use executor::{
+    check_async, check_async_cfg, property_async, property_async_cfg, BlockOn, Executor,
+};
+pub use fuzz::{fuzz, fuzz_cfg};
+pub use parallel::{check_parallel, property_parallel};
+pub use properties::{
+    differential, differential_by, differential_cfg, roundtrip, roundtrip_cfg, Metamorphic,
+};
+pub use replay::{check_replay, replay, with_replay, Replay};
+pub use report::{Failure, NamedValue, Stats};
+pub use search::Search;
 
 #[derive(Clone)]
 pub struct Shrinkable<T> {
@@ -100,23 +134,24 @@ where
 struct Generated<T> {
     name: &'static str,
     value: RefCell<Shrinkable<T>>,
+    json: Option<JsonCodec<T>>,
 }
 
-impl<T> Display for Generated<T>
-where
-    T: Debug,
-{
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}: {} = {:#?}",
-            self.name,
-            type_name::<T>(),
-            self.value.borrow().value
-        )
+/// Converts values to and from JSON, for values
+/// generated with [Maat::generate_serializable].
+struct JsonCodec<T> {
+    to_json: fn(&T) -> Option<serde_json::Value>,
+    from_json: fn(&serde_json::Value) -> Option<T>,
+}
+
+impl<T> Clone for JsonCodec<T> {
+    fn clone(&self) -> Self {
+        *self
     }
 }
 
+impl<T> Copy for JsonCodec<T> {}
+
 impl<T> GeneratedValue for Generated<T>
 where
     T: 'static + Clone + Debug,
@@ -144,15 +179,29 @@ where
     fn type_name(&self) -> &'static str {
         type_name::<T>()
     }
+
+    fn named(&self) -> NamedValue {
+        NamedValue {
+            name: self.name,
+            type_name: type_name::<T>(),
+            value: Box::new(self.value.borrow().value.clone()),
+            json: self
+                .json
+                .and_then(|json| (json.to_json)(&self.value.borrow().value)),
+        }
+    }
 }
 
 /// GeneratedValue exists to hide the real type
 /// and allow for heterogenous values in the [Recording].
-trait GeneratedValue: Display {
+trait GeneratedValue {
     fn name(&self) -> &'static str;
     fn type_name(&self) -> &'static str;
     fn value(&self) -> Box<Dynamic>;
 
+    /// Takes a snapshot of the current value, for reporting.
+    fn named(&self) -> NamedValue;
+
     // Attempts to shrink the internal value, mutably:
     fn shrink(&self, shrink_valid: &mut dyn FnMut() -> bool) -> bool;
 }
@@ -164,9 +213,33 @@ pub struct Maat<'a> {
     // this type serves only to hide the Mode type,
     // which would otherwise have public members
     mode: Mode<'a>,
+    observations: Observations,
+}
+
+/// What the property has reported about itself,
+/// beyond the values it generated.
+#[derive(Default)]
+struct Observations {
+    /// Hashes of features passed to [Maat::observe].
+    features: Vec<u64>,
+    /// Values passed to [Maat::target].
+    targets: Vec<(&'static str, f64)>,
+    /// Labels given to this iteration by [Maat::label].
+    labels: Vec<String>,
+    /// Coverage required by [Maat::cover], as a percentage.
+    required: Vec<(String, f64)>,
+    /// Values passed to [Maat::note].
+    notes: Vec<NamedValue>,
 }
 
 impl<'a> Maat<'a> {
+    fn new(mode: Mode<'a>) -> Self {
+        Maat {
+            mode,
+            observations: Observations::default(),
+        }
+    }
+
     /// Generate a random value with the given `name`
     /// and using the given `generator`.
     ///
@@ -182,7 +255,142 @@ impl<'a> Maat<'a> {
     where
         T: Debug + Clone + 'static,
     {
-        self.mode.generate(name, generator)
+        self.mode.generate(name, generator, None)
+    }
+
+    /// Like [Maat::generate], but the value is also included as JSON
+    /// (rather than only its [Debug] representation) in the JSON output,
+    /// and can be replayed with a [Replay].
+    #[inline(always)]
+    pub fn generate_serializable<T>(
+        &mut self,
+        name: &'static str,
+        generator: impl Generator<T>,
+    ) -> T
+    where
+        T: Debug + Clone + serde::Serialize + serde::de::DeserializeOwned + 'static,
+    {
+        self.mode.generate(
+            name,
+            generator,
+            Some(JsonCodec {
+                to_json: |value| serde_json::to_value(value).ok(),
+                from_json: |json| serde_json::from_value(json.clone()).ok(),
+            }),
+        )
+    }
+
+    /// Record that the property reached some interesting `feature`,
+    /// such as a branch taken or a state visited.
+    ///
+    /// Features are used by [Search::CoverageGuided] to decide which
+    /// inputs are worth mutating further; otherwise they are ignored.
+    /// Features observed inside a [generators::derive] are not seen.
+    ///
+    /// # Example
+    /// ```rust
+    /// # maat::property(|maat| {
+    /// # let tokens: Vec<&str> = "1 + 2".split(' ').collect();
+    /// maat.observe(("parsed", tokens.len()));
+    /// # true
+    /// # });
+    /// ```
+    pub fn observe(&mut self, feature: impl Hash) {
+        let mut hasher = DefaultHasher::new();
+        feature.hash(&mut hasher);
+        self.observations.features.push(hasher.finish());
+    }
+
+    /// Report a metric which [Search::Targeted] should try to maximize,
+    /// such as the length of a probe sequence or the depth of a queue.
+    ///
+    /// Otherwise this is ignored, as are metrics reported inside
+    /// a [generators::derive].
+    ///
+    /// # Example
+    /// ```rust
+    /// # maat::property(|maat| {
+    /// # struct Table;
+    /// # impl Table { fn probe_len(&self, _key: u64) -> usize { 1 } }
+    /// # let (table, key) = (Table, 7);
+    /// maat.target("probe_len", table.probe_len(key) as f64);
+    /// # true
+    /// # });
+    /// ```
+    pub fn target(&mut self, label: &'static str, value: f64) {
+        self.observations.targets.push((label, value));
+    }
+
+    /// Attach a label to this iteration. When the property passes,
+    /// the percentage of iterations with each label is reported.
+    pub fn label(&mut self, label: impl Into<String>) {
+        self.observations.labels.push(label.into());
+    }
+
+    /// Attach a label to this iteration if `condition` holds.
+    pub fn classify(&mut self, condition: bool, label: impl Into<String>) {
+        if condition {
+            self.label(label);
+        }
+    }
+
+    /// Label this iteration with the [Debug] representation of `value`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use maat::generators::usize;
+    /// # maat::property(|maat| {
+    /// # let list: Vec<usize> = (0..maat.generate("len", usize(0, 3))).collect();
+    /// maat.collect(list.len());
+    /// # true
+    /// # });
+    /// ```
+    pub fn collect(&mut self, value: impl Debug) {
+        self.label(format!("{value:?}"));
+    }
+
+    /// Like [Maat::classify], but the property fails unless at least
+    /// `percentage` percent of iterations have the label.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use maat::generators::usize;
+    /// # maat::property(|maat| {
+    /// # let list: Vec<usize> = (0..maat.generate("len", usize(0, 3))).collect();
+    /// maat.cover(5.0, list.is_empty(), "empty");
+    /// # true
+    /// # });
+    /// ```
+    pub fn cover(&mut self, percentage: f64, condition: bool, label: impl Into<String>) {
+        let label = label.into();
+        self.observations.required.push((label.clone(), percentage));
+        self.classify(condition, label);
+    }
+
+    /// Record a value computed by the property, such as an intermediate
+    /// result, to be shown alongside the shrunk values if it fails.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use maat::generators::i64;
+    /// # maat::property(|maat| {
+    /// # let value = maat.generate("value", i64(0, 10));
+    /// let encoded = serde_json::to_string(&value).unwrap();
+    /// maat.note("encoded", encoded.clone());
+    /// # true
+    /// # });
+    /// ```
+    pub fn note<T: Debug + 'static>(&mut self, name: &'static str, value: T) {
+        // notes are only displayed for failures,
+        // so there's no need to keep them while testing
+        if !matches!(self.mode, Mode::Testing { .. }) {
+            self.observations.notes.push(NamedValue {
+                name,
+                type_name: type_name::<T>(),
+                value: Box::new(value),
+                json: None,
+            });
+        }
     }
 }
 
@@ -198,10 +406,20 @@ enum Mode<'a> {
         recording_ix: usize,
         recording: &'a Recording,
     },
+    Replaying {
+        replay: &'a Replay,
+        replay_ix: usize,
+        record: &'a mut Vec<NamedValue>,
+    },
 }
 
 impl<'a> Mode<'a> {
-    pub fn generate<T>(&mut self, name: &'static str, generator: impl Generator<T>) -> T
+    pub fn generate<T>(
+        &mut self,
+        name: &'static str,
+        generator: impl Generator<T>,
+        json: Option<JsonCodec<T>>,
+    ) -> T
     where
         T: Clone + std::fmt::Debug + 'static,
     {
@@ -213,6 +431,7 @@ impl<'a> Mode<'a> {
                 record.push(Box::new(Generated {
                     name,
                     value: RefCell::new(shrinkable),
+                    json,
                 }));
 
                 result
@@ -239,12 +458,138 @@ impl<'a> Mode<'a> {
                     panic!("[maat] Usage error: while shrinking, got a different type for generated value {at}: was {old}, is {new}");
                 }
             }
+            Mode::Replaying {
+                replay,
+                replay_ix: at,
+                record,
+            } => {
+                let value = replay.get(*at, name, json);
+                *at += 1;
+                let result = value
+                    .downcast_ref::<T>()
+                    .expect("replayed value has the requested type")
+                    .clone();
+                record.push(value);
+                result
+            }
         }
     }
 }
 
 pub struct Config {
     iterations: usize,
+    seed: Option<u64>,
+    name: Option<&'static str>,
+    json_output: Option<PathBuf>,
+    regression_output: Option<PathBuf>,
+    formatter: fn(&dyn Debug) -> String,
+    short_type_names: bool,
+    max_value_length: Option<usize>,
+    threads: Option<usize>,
+    search: Search,
+    input: Option<Vec<u8>>,
+}
+
+impl Config {
+    /// Sets the number of iterations to run.
+    pub fn iterations(self, iterations: usize) -> Self {
+        Config { iterations, ..self }
+    }
+
+    /// Sets the seed for the RNG, to reproduce a previous run.
+    /// By default a new seed is chosen for every run.
+    ///
+    /// To rerun exactly the values of a previous failure, see [Replay].
+    pub fn seed(self, seed: u64) -> Self {
+        Config {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Sets the name of the property, as used in the JSON output.
+    /// By default this is the name of the current thread, which
+    /// is the name of the test when running under `cargo test`.
+    pub fn name(self, name: &'static str) -> Self {
+        Config {
+            name: Some(name),
+            ..self
+        }
+    }
+
+    /// Appends a JSON record describing each run to the file at `path`,
+    /// or writes it to stdout if `path` is `-`.
+    ///
+    /// If this is not set, the `MAAT_JSON_OUTPUT` environment
+    /// variable is used instead.
+    pub fn json_output(self, path: impl Into<PathBuf>) -> Self {
+        Config {
+            json_output: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// On failure, appends a regression test for the shrunk values
+    /// (see [Failure::regression_test]) to the file at `path`,
+    /// or writes it to stdout if `path` is `-`.
+    ///
+    /// If this is not set, the `MAAT_REGRESSION_OUTPUT` environment
+    /// variable is used instead.
+    pub fn regression_output(self, path: impl Into<PathBuf>) -> Self {
+        Config {
+            regression_output: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Sets the function used to format values when reporting a failure.
+    /// By default this is the pretty-printed [Debug] representation.
+    pub fn formatter(self, formatter: fn(&dyn Debug) -> String) -> Self {
+        Config { formatter, ..self }
+    }
+
+    /// Displays types without their module paths
+    /// (e.g. `Vec<i64>` rather than `alloc::vec::Vec<i64>`).
+    pub fn short_type_names(self, short_type_names: bool) -> Self {
+        Config {
+            short_type_names,
+            ..self
+        }
+    }
+
+    /// Truncates formatted values that are longer than `length` characters.
+    pub fn max_value_length(self, length: usize) -> Self {
+        Config {
+            max_value_length: Some(length),
+            ..self
+        }
+    }
+
+    /// Sets the number of threads used by [check_parallel].
+    /// By default this is the available parallelism of the machine.
+    pub fn threads(self, threads: usize) -> Self {
+        Config {
+            threads: Some(threads),
+            ..self
+        }
+    }
+
+    /// Sets how inputs are chosen by [check_cfg].
+    /// By default they are generated independently at random.
+    pub fn search(self, search: Search) -> Self {
+        Config { search, ..self }
+    }
+
+    /// Runs the property once, generating values from the bytes of `input`
+    /// rather than from the RNG (see [BufferRng]). On failure, the input is
+    /// shrunk by deleting and zeroing bytes, and is reported in
+    /// [Failure::buffer].
+    pub fn input(self, input: impl Into<Vec<u8>>) -> Self {
+        Config {
+            input: Some(input.into()),
+            ..self
+        }
+    }
 }
 
 impl Default for Config {
@@ -253,50 +598,230 @@ impl Default for Config {
     }
 }
 
-const DEFAULT_CONFIG: Config = Config { iterations: 100 };
+const DEFAULT_CONFIG: Config = Config {
+    iterations: 100,
+    seed: None,
+    name: None,
+    json_output: None,
+    regression_output: None,
+    formatter: |value| format!("{value:#?}"),
+    short_type_names: false,
+    max_value_length: None,
+    threads: None,
+    search: Search::Random,
+    input: None,
+};
 
+/// Checks the property, panicking with a description
+/// of the counterexample if it is falsified.
 pub fn property(test: impl Fn(&mut Maat) -> bool) {
     property_cfg(test, &DEFAULT_CONFIG);
 }
 
 pub fn property_cfg(test: impl Fn(&mut Maat) -> bool, cfg: &Config) {
-    // TODO: replay stored RNG values for regression-checks
-    let mut rng = RNG::from_entropy();
+    print_result(check_cfg(test, cfg));
+}
+
+fn print_result(result: Result<Stats, Failure>) {
+    match result {
+        Ok(stats) => {
+            println!(
+                "[maat] OK, passed {} tests ({:.0} iterations/sec)",
+                stats.iterations,
+                stats.iterations_per_sec()
+            );
+            print!("{}", stats.distribution());
+        }
+        Err(failure) => panic!("{failure}"),
+    }
+}
+
+/// Checks the property, returning the counterexample
+/// if it is falsified.
+pub fn check(test: impl Fn(&mut Maat) -> bool) -> Result<Stats, Failure> {
+    check_cfg(test, &DEFAULT_CONFIG)
+}
+
+pub fn check_cfg(test: impl Fn(&mut Maat) -> bool, cfg: &Config) -> Result<Stats, Failure> {
     let start = Instant::now();
-    for _ in 0..cfg.iterations {
+    let result = run_iterations(test, cfg);
+    write_outputs(cfg, start, &result);
+    result
+}
+
+/// Writes any outputs requested by the [Config].
+fn write_outputs(cfg: &Config, start: Instant, result: &Result<Stats, Failure>) {
+    let name = property_name(cfg);
+
+    if let Some(path) = output_path(&cfg.json_output, "MAAT_JSON_OUTPUT") {
+        let record = report::json_record(&name, start.elapsed(), result);
+        report::write_output(&path, &format!("{record}\n"));
+    }
+
+    if let (Err(failure), Some(path)) = (
+        result,
+        output_path(&cfg.regression_output, "MAAT_REGRESSION_OUTPUT"),
+    ) {
+        // use only the final part of the (test) name,
+        // so that it's a valid identifier
+        let test_name = name.rsplit("::").next().unwrap_or_default();
+        report::write_output(&path, &failure.regression_test(test_name));
+    }
+}
+
+/// The name of the property: see [Config::name].
+fn property_name(cfg: &Config) -> String {
+    cfg.name
+        .map(str::to_string)
+        .or_else(|| std::thread::current().name().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn output_path(configured: &Option<PathBuf>, env_var: &str) -> Option<PathBuf> {
+    configured
+        .clone()
+        .or_else(|| std::env::var_os(env_var).map(PathBuf::from))
+}
+
+thread_local! {
+    /// Whether panics on this thread are being caught by [catch_panic].
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Calls `f`, returning the panic message (if it was a string) if it panics.
+/// Caught panics aren't passed to the panic hook, so aren't printed, and
+/// don't abort the process under libFuzzer.
+fn catch_panic<R>(f: impl FnOnce() -> R) -> Result<R, Option<String>> {
+    static QUIET_HOOK: Once = Once::new();
+    QUIET_HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING_PANICS.get() {
+                hook(info);
+            }
+        }));
+    });
+
+    let catching = CATCHING_PANICS.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING_PANICS.set(catching);
+    result.map_err(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+    })
+}
+
+fn run_iterations(test: impl Fn(&mut Maat) -> bool, cfg: &Config) -> Result<Stats, Failure> {
+    if let Some(replay) = Replay::current(&property_name(cfg)) {
+        return replay::run_replay(test, &replay, cfg);
+    }
+
+    if let Some(input) = &cfg.input {
+        return fuzz::run_input(test, input, cfg);
+    }
+
+    let seed = cfg.seed.unwrap_or_else(rand::random);
+    if cfg.search != Search::Random {
+        return search::run_guided(test, seed, cfg);
+    }
+
+    let mut rng = RNG::seed_from_u64(seed);
+    let mut coverage = Coverage::default();
+    let start = Instant::now();
+    for iteration in 0..cfg.iterations {
         // store RNG state so we can reuse it for recording, if needed
         let iteration_rng = rng.clone();
-        let mode = Mode::Testing { rng: &mut rng };
-        if !test(&mut Maat { mode }) {
-            handle_failure(test, iteration_rng);
+        let mut maat = Maat::new(Mode::Testing { rng: &mut rng });
+        if !test(&mut maat) {
+            return Err(handle_failure(
+                test,
+                iteration_rng,
+                Some(seed),
+                iteration,
+                cfg,
+            ));
         }
+
+        coverage.add(&maat.observations);
     }
 
-    let elapsed = start.elapsed();
-    println!(
-        "[maat] OK, passed {} tests ({:.0} iterations/sec)",
-        cfg.iterations,
-        cfg.iterations as f64 / elapsed.as_secs_f64()
-    );
+    coverage.finish(seed, cfg.iterations, start.elapsed())
 }
 
 #[cold]
-fn handle_failure(test: impl Fn(&mut Maat) -> bool, rng: RNG) -> ! {
-    let original = make_recording(&test, rng);
-    let original_str = display_recording(&original);
-    let shrunk = shrink_recording(&test, original);
-    let shrunk_str = display_recording(&shrunk);
-    panic!("\n[maat] Falsified property with values:\n{shrunk_str}\n\n[maat] Original failing values were:\n{original_str}\n");
+fn handle_failure(
+    test: impl Fn(&mut Maat) -> bool,
+    rng: impl rand::RngCore,
+    seed: Option<u64>,
+    iteration: usize,
+    cfg: &Config,
+) -> Failure {
+    let recording = make_recording(&test, rng);
+    let original = snapshot_recording(&recording);
+    shrink_failure(test, recording, original, seed, iteration, cfg)
+}
+
+/// Shrinks the `recording` of a failure, which was
+/// originally found with the `original` values.
+fn shrink_failure(
+    test: impl Fn(&mut Maat) -> bool,
+    recording: Recording,
+    original: Vec<NamedValue>,
+    seed: Option<u64>,
+    iteration: usize,
+    cfg: &Config,
+) -> Failure {
+    let start = Instant::now();
+    let (shrunk, shrink_steps) = shrink_recording(&test, recording);
+    let shrink_time = start.elapsed();
+    let notes = replay_notes(&test, &shrunk);
+    let shrunk = snapshot_recording(&shrunk);
+    let original_str = report::display_values(&original, cfg);
+    let mut shrunk_str = report::display_values(&shrunk, cfg);
+    if !notes.is_empty() {
+        let notes_str = report::display_values(&notes, cfg);
+        write!(shrunk_str, "\n[maat] Noted by the property:\n{notes_str}").unwrap();
+    }
+
+    let found = match seed {
+        Some(seed) => format!("Found at iteration {iteration} with seed {seed}"),
+        None => "Found from the input buffer".to_string(),
+    };
+
+    let mut failure = Failure {
+        seed,
+        iteration,
+        shrink_steps,
+        shrink_time,
+        original,
+        shrunk,
+        buffer: None,
+        message: format!("\n[maat] Falsified property with values:\n{shrunk_str}\n\n[maat] Original failing values were:\n{original_str}\n\n[maat] {found}, after {shrink_steps} shrink steps\n"),
+    };
+
+    let replay = failure.replay();
+    let name = property_name(cfg);
+    if let Some(json) = replay.and_then(|replay| replay.property(&name).to_json()) {
+        write!(
+            failure.message,
+            "\n[maat] To replay the shrunk values, set MAAT_REPLAY='{json}'\n"
+        )
+        .unwrap();
+    }
+
+    failure
 }
 
-fn make_recording(test: impl Fn(&mut Maat) -> bool, mut rng: RNG) -> Recording {
+fn make_recording(test: impl Fn(&mut Maat) -> bool, mut rng: impl rand::RngCore) -> Recording {
     let mut record = Vec::new();
     let mode = Mode::Recording {
         rng: &mut rng,
         record: &mut record,
     };
 
-    let recording_passed = test(&mut Maat { mode });
+    let recording_passed = test(&mut Maat::new(mode));
     if recording_passed {
         panic!("[maat] Non-deterministic test function: found a failure but was unable to reproduce it.");
     }
@@ -304,19 +829,24 @@ fn make_recording(test: impl Fn(&mut Maat) -> bool, mut rng: RNG) -> Recording {
     record
 }
 
-fn shrink_recording(test: impl Fn(&mut Maat) -> bool, recording: Recording) -> Recording {
+fn shrink_recording(test: impl Fn(&mut Maat) -> bool, recording: Recording) -> (Recording, usize) {
+    let steps = Cell::new(0);
     loop {
         let mut shrank_any = false;
         // attempt to shrink each value in the recording
         for value in &recording {
             while value.shrink(&mut || {
                 // the shrink is valid if test still fails
-                !test(&mut Maat {
-                    mode: Mode::Shrinking {
-                        recording_ix: 0,
-                        recording: &recording,
-                    },
-                })
+                let valid = !test(&mut Maat::new(Mode::Shrinking {
+                    recording_ix: 0,
+                    recording: &recording,
+                }));
+
+                if valid {
+                    steps.set(steps.get() + 1);
+                }
+
+                valid
             }) {
                 shrank_any = true;
             }
@@ -329,16 +859,23 @@ fn shrink_recording(test: impl Fn(&mut Maat) -> bool, recording: Recording) -> R
         }
     }
 
-    recording
+    (recording, steps.get())
 }
 
-fn display_recording(recording: &Recording) -> String {
-    let mut result = String::new();
-    for value in recording {
-        writeln!(result, "{value}").unwrap();
-    }
+/// Runs the test once more against the recording,
+/// returning the values passed to [Maat::note].
+fn replay_notes(test: impl Fn(&mut Maat) -> bool, recording: &Recording) -> Vec<NamedValue> {
+    let mut maat = Maat::new(Mode::Shrinking {
+        recording_ix: 0,
+        recording,
+    });
 
-    result
+    test(&mut maat);
+    maat.observations.notes
+}
+
+fn snapshot_recording(recording: &Recording) -> Vec<NamedValue> {
+    recording.iter().map(|value| value.named()).collect()
 }
 
 #[cfg(test)]
@@ -364,6 +901,87 @@ mod test {
         })
     }
 
+    #[test]
+    pub fn check_reports_shrunk_values() {
+        let failure = check(|maat| {
+            let x = maat.generate("x", i64(0, 100));
+            x < 10
+        })
+        .unwrap_err();
+
+        assert_eq!(failure.shrunk.len(), 1);
+        assert_eq!(failure.shrunk[0].name(), "x");
+        assert_eq!(failure.shrunk[0].downcast_ref::<i64>(), Some(&10));
+        assert!(failure.shrink_steps > 0);
+    }
+
+    #[test]
+    pub fn json_record_includes_serializable_values() {
+        let mut result = check(|maat| {
+            let x = maat.generate_serializable("x", i64(0, 100));
+            let y = maat.generate("y", i64(0, 100));
+            x < 10 || y < 0
+        });
+
+        if let Err(failure) = &mut result {
+            failure.shrink_time = std::time::Duration::from_millis(750);
+        }
+
+        let record = report::json_record("json", std::time::Duration::from_secs(1), &result);
+        assert_eq!(record["passed"], false);
+        let iterations = record["iterations"].as_f64().unwrap();
+        assert_eq!(record["iterations_per_sec"], iterations * 4.0);
+        assert_eq!(record["shrink_secs"], 0.75);
+        assert_eq!(record["shrunk"][0]["value"], 10);
+        assert_eq!(record["shrunk"][1]["value"], serde_json::Value::Null);
+        assert_eq!(record["shrunk"][1]["debug"], "0");
+    }
+
+    #[test]
+    pub fn replay_shrunk_values() {
+        let test = |maat: &mut Maat| {
+            let x = maat.generate_serializable("x", vec(i64(0, 100), 0, 10));
+            x.iter().sum::<i64>() < 100
+        };
+
+        let failure = check(test).unwrap_err();
+        let json = failure.replay().unwrap().to_json().unwrap();
+        let replay = Replay::from_json(&json).unwrap();
+        let replayed = check_replay(test, &replay).unwrap_err();
+        assert_eq!(
+            replayed.shrunk[0].downcast_ref::<Vec<i64>>(),
+            failure.shrunk[0].downcast_ref::<Vec<i64>>()
+        );
+    }
+
+    #[test]
+    pub fn replay_from_env_only_applies_to_its_property() {
+        let cfg = Config::default().name("first");
+        let failure = check_cfg(
+            |maat| maat.generate_serializable("x", i64(0, 100)) < 10,
+            &cfg,
+        )
+        .unwrap_err();
+
+        let message = failure.to_string();
+        let json = message
+            .split("MAAT_REPLAY='")
+            .nth(1)
+            .and_then(|rest| rest.split('\'').next())
+            .unwrap();
+
+        assert!(Replay::from_var(json.to_string(), "second").is_none());
+        let replay = Replay::from_var(json.to_string(), "first").unwrap();
+        let replayed = check_replay(
+            |maat| maat.generate_serializable("x", i64(0, 100)) < 10,
+            &replay,
+        );
+        assert_eq!(
+            replayed.unwrap_err().shrunk[0].downcast_ref::<i64>(),
+            Some(&10)
+        );
+    }
+
     #[test]
     pub fn test_string_from_example() {
         property(|maat| {
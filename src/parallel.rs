@@ -0,0 +1,155 @@
+//! Running iterations on multiple threads.
+//!
+//! Only [Mode::Testing] runs in parallel: each worker thread gets its own
+//! RNG stream, made by jumping the seeded RNG ahead once per worker. Once a
+//! failure is found, recording and shrinking happen on the calling thread
+//! exactly as they do for [crate::check_cfg], so the [Rc]-based shrinking
+//! machinery never needs to cross threads.
+//!
+//! [Rc]: std::rc::Rc
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use rand::SeedableRng;
+
+use crate::{
+    coverage::Coverage, handle_failure, print_result, property_name, replay, run_iterations,
+    write_outputs, Config, Failure, Maat, Mode, Replay, Search, Stats, RNG,
+};
+
+/// Like [crate::property_cfg], but runs iterations on multiple threads.
+pub fn property_parallel(test: impl Fn(&mut Maat) -> bool + Sync, cfg: &Config) {
+    print_result(check_parallel(test, cfg));
+}
+
+/// Like [crate::check_cfg], but runs iterations on multiple threads
+/// (see [Config::threads]).
+///
+/// Iterations are spread across threads so that iteration `i` is run by
+/// thread `i % threads`. The failure with the lowest iteration number is
+/// the one reported, so a run with the same seed and number of threads
+/// will always find the same failure.
+///
+/// Guided searches (see [Config::search]) and fixed inputs (see
+/// [Config::input]) are run on the calling thread, as by [crate::check_cfg].
+pub fn check_parallel(
+    test: impl Fn(&mut Maat) -> bool + Sync,
+    cfg: &Config,
+) -> Result<Stats, Failure> {
+    let start = Instant::now();
+    let result = run_parallel(test, cfg);
+    write_outputs(cfg, start, &result);
+    result
+}
+
+fn run_parallel(test: impl Fn(&mut Maat) -> bool + Sync, cfg: &Config) -> Result<Stats, Failure> {
+    if let Some(replay) = Replay::current(&property_name(cfg)) {
+        return replay::run_replay(test, &replay, cfg);
+    }
+
+    // each iteration of these depends on the previous ones
+    if cfg.input.is_some() || cfg.search != Search::Random {
+        return run_iterations(test, cfg);
+    }
+
+    let threads = cfg
+        .threads
+        .or_else(|| std::thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+        .max(1);
+
+    let seed = cfg.seed.unwrap_or_else(rand::random);
+    let start = Instant::now();
+
+    // the lowest iteration that has failed so far;
+    // workers stop once they pass this point
+    let first_failure = AtomicUsize::new(usize::MAX);
+    let failures = Mutex::new(Vec::new());
+
+    let mut rng = RNG::seed_from_u64(seed);
+    let mut coverage = Coverage::default();
+    std::thread::scope(|scope| {
+        let mut workers = Vec::new();
+        for worker in 0..threads {
+            let mut worker_rng = rng.clone();
+            rng.jump();
+
+            let (test, first_failure, failures) = (&test, &first_failure, &failures);
+            workers.push(scope.spawn(move || {
+                let mut coverage = Coverage::default();
+                for iteration in (worker..cfg.iterations).step_by(threads) {
+                    if iteration > first_failure.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    // store RNG state so we can reuse it for recording, if needed
+                    let iteration_rng = worker_rng.clone();
+                    let mut maat = Maat::new(Mode::Testing {
+                        rng: &mut worker_rng,
+                    });
+
+                    if !test(&mut maat) {
+                        first_failure.fetch_min(iteration, Ordering::Relaxed);
+                        failures.lock().unwrap().push((iteration, iteration_rng));
+                        break;
+                    }
+
+                    coverage.add(&maat.observations);
+                }
+
+                coverage
+            }));
+        }
+
+        for worker in workers {
+            coverage.merge(worker.join().unwrap());
+        }
+    });
+
+    let failure = failures
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .min_by_key(|(iteration, _)| *iteration);
+
+    match failure {
+        Some((iteration, rng)) => Err(handle_failure(test, rng, Some(seed), iteration, cfg)),
+        None => coverage.finish(seed, cfg.iterations, start.elapsed()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generators::i64;
+
+    #[test]
+    pub fn failures_are_deterministic() {
+        let cfg = Config::default().seed(1234).threads(4).iterations(10_000);
+        let test = |maat: &mut Maat| {
+            let x = maat.generate("x", i64(0, 10_000));
+            x < 9_990
+        };
+
+        let first = check_parallel(test, &cfg).unwrap_err();
+        let second = check_parallel(test, &cfg).unwrap_err();
+        assert_eq!(first.iteration, second.iteration);
+        assert_eq!(
+            first.original[0].downcast_ref::<i64>(),
+            second.original[0].downcast_ref::<i64>()
+        );
+    }
+
+    #[test]
+    pub fn inputs_run_on_one_thread() {
+        let cfg = Config::default().threads(4).input([1, 2, 3]);
+        let stats = check_parallel(|maat| maat.generate("x", i64(0, 10)) < 10, &cfg).unwrap();
+        assert_eq!(stats.iterations, 1);
+    }
+}
@@ -0,0 +1,331 @@
+//! Ready-made properties for common kinds of test.
+
+use std::{fmt::Debug, rc::Rc};
+
+use crate::{catch_panic, property_cfg, Config, Generator, Maat, DEFAULT_CONFIG};
+
+/// Checks that decoding the encoding of each generated value gives back
+/// the original value, panicking with a description of the shrunk
+/// counterexample (including its encoded and decoded forms) if not.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::deserializable, roundtrip};
+/// # use serde::{Deserialize, Serialize};
+/// # #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// # struct Config { name: String, retries: Option<u8> }
+/// roundtrip(
+///     deserializable::<Config>(),
+///     |config| serde_json::to_string(config).unwrap(),
+///     |json| serde_json::from_str(json),
+/// );
+/// ```
+pub fn roundtrip<T, E, D>(
+    generator: impl Generator<T>,
+    encode: impl Fn(&T) -> E,
+    decode: impl Fn(&E) -> Result<T, D>,
+) where
+    T: Debug + Clone + PartialEq + 'static,
+    E: Debug + 'static,
+    D: Debug + 'static,
+{
+    roundtrip_cfg(generator, encode, decode, &DEFAULT_CONFIG);
+}
+
+pub fn roundtrip_cfg<T, E, D>(
+    generator: impl Generator<T>,
+    encode: impl Fn(&T) -> E,
+    decode: impl Fn(&E) -> Result<T, D>,
+    cfg: &Config,
+) where
+    T: Debug + Clone + PartialEq + 'static,
+    E: Debug + 'static,
+    D: Debug + 'static,
+{
+    property_cfg(
+        |maat| {
+            let value = maat.generate("value", &generator);
+            let encoded = encode(&value);
+            let decoded = decode(&encoded);
+            let passed = matches!(&decoded, Ok(decoded) if *decoded == value);
+            maat.note("encoded", encoded);
+            maat.note("decoded", decoded);
+            passed
+        },
+        cfg,
+    );
+}
+
+/// Checks that `candidate` gives the same output as `reference` for each
+/// generated input, panicking with a description of the shrunk input
+/// (and both outputs) if not.
+///
+/// Panics are caught and compared too: the implementations agree if both
+/// panic, and disagree if only one does. Caught panics aren't printed.
+///
+/// # Example
+/// ```rust
+/// # use maat::{differential, generators::{derive, i64}, Generator};
+/// # fn keys() -> impl Generator<Vec<i64>> {
+/// #     derive(|maat| vec![maat.generate("key", i64(0, 10))])
+/// # }
+/// # fn naive_lookup(keys: &[i64]) -> usize { keys.len() }
+/// # fn table_lookup(keys: &[i64]) -> usize { keys.len() }
+/// differential(keys(), |keys| naive_lookup(keys), |keys| table_lookup(keys));
+/// ```
+pub fn differential<I, O>(
+    generator: impl Generator<I>,
+    reference: impl Fn(&I) -> O,
+    candidate: impl Fn(&I) -> O,
+) where
+    I: Debug + Clone + 'static,
+    O: Debug + PartialEq + 'static,
+{
+    differential_cfg(generator, reference, candidate, O::eq, &DEFAULT_CONFIG);
+}
+
+/// Like [differential], but outputs are compared with `same` rather than
+/// [PartialEq], such as to allow for rounding errors.
+pub fn differential_by<I, O>(
+    generator: impl Generator<I>,
+    reference: impl Fn(&I) -> O,
+    candidate: impl Fn(&I) -> O,
+    same: impl Fn(&O, &O) -> bool,
+) where
+    I: Debug + Clone + 'static,
+    O: Debug + 'static,
+{
+    differential_cfg(generator, reference, candidate, same, &DEFAULT_CONFIG);
+}
+
+pub fn differential_cfg<I, O>(
+    generator: impl Generator<I>,
+    reference: impl Fn(&I) -> O,
+    candidate: impl Fn(&I) -> O,
+    same: impl Fn(&O, &O) -> bool,
+    cfg: &Config,
+) where
+    I: Debug + Clone + 'static,
+    O: Debug + 'static,
+{
+    property_cfg(
+        |maat| {
+            let input = maat.generate("input", &generator);
+            let expected = Outcome::of(|| reference(&input));
+            let actual = Outcome::of(|| candidate(&input));
+            let passed = match (&expected, &actual) {
+                (Outcome::Returned(expected), Outcome::Returned(actual)) => same(expected, actual),
+                (Outcome::Panicked(_), Outcome::Panicked(_)) => true,
+                _ => false,
+            };
+
+            expected.note(maat, "reference");
+            actual.note(maat, "candidate");
+            passed
+        },
+        cfg,
+    );
+}
+
+/// The result of calling an implementation, including if it panicked.
+enum Outcome<O> {
+    Returned(O),
+    /// The panic message, if it was a string.
+    Panicked(Option<String>),
+}
+
+impl<O: Debug + 'static> Outcome<O> {
+    fn of(f: impl FnOnce() -> O) -> Self {
+        match catch_panic(f) {
+            Ok(output) => Outcome::Returned(output),
+            Err(message) => Outcome::Panicked(message),
+        }
+    }
+
+    /// Notes the output, or the panic message if it panicked.
+    fn note(self, maat: &mut Maat, name: &'static str) {
+        match self {
+            Outcome::Returned(output) => maat.note(name, output),
+            Outcome::Panicked(message) => maat.note(
+                name,
+                format!("panicked: {}", message.as_deref().unwrap_or("<unknown>")),
+            ),
+        }
+    }
+}
+
+/// A property made of relations between the outputs of a system, for
+/// systems without an oracle: for example, that adding a filter to a query
+/// never increases the number of results.
+///
+/// Each relation generates a transformation, and applies it to the generated
+/// input to give a follow-up input. The relation must hold between the
+/// outputs for the source and follow-up inputs.
+///
+/// # Example
+/// ```rust
+/// # use maat::{generators::{derive, i64}, Metamorphic};
+/// # #[derive(Clone, Debug)]
+/// # struct Query(Vec<i64>);
+/// # impl Query {
+/// #     fn and(&self, filter: &i64) -> Query { Query([&self.0[..], &[*filter]].concat()) }
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     fn count(&self, query: &Query) -> usize {
+/// #         (0..100).filter(|x| query.0.iter().all(|f| x % f == 0)).count()
+/// #     }
+/// # }
+/// # let db = Db;
+/// # let queries = || derive(|maat| Query(vec![maat.generate("filter", i64(1, 10))]));
+/// # let filters = || i64(1, 10);
+/// Metamorphic::new(queries(), move |query| db.count(query))
+///     .relation("filter", filters(), |query, filter| query.and(filter), |source, followup| followup <= source)
+///     .property();
+/// ```
+pub struct Metamorphic<I, O> {
+    input: Box<dyn Generator<I>>,
+    system: Rc<dyn Fn(&I) -> O>,
+    relations: Vec<Relation<I, O>>,
+}
+
+/// Generates a transformation, returning the follow-up input
+/// and output if the relation does not hold for them.
+type Relation<I, O> = Box<dyn Fn(&mut Maat, &I, &O) -> Option<(I, O)>>;
+
+impl<I, O> Metamorphic<I, O>
+where
+    I: Debug + Clone + 'static,
+    O: Debug + 'static,
+{
+    pub fn new(input: impl Generator<I> + 'static, system: impl Fn(&I) -> O + 'static) -> Self {
+        Metamorphic {
+            input: Box::new(input),
+            system: Rc::new(system),
+            relations: Vec::new(),
+        }
+    }
+
+    /// Adds a relation, which generates a transformation with the given
+    /// `name` and `generator`, and applies it to the source input with
+    /// `transform`. Then `holds` is given the source and follow-up outputs.
+    pub fn relation<T: Debug + Clone + 'static>(
+        self,
+        name: &'static str,
+        generator: impl Generator<T> + 'static,
+        transform: impl Fn(&I, &T) -> I + 'static,
+        holds: impl Fn(&O, &O) -> bool + 'static,
+    ) -> Self {
+        let system = self.system.clone();
+        let mut relations = self.relations;
+        relations.push(Box::new(move |maat, input, output| {
+            let transformation = maat.generate(name, &generator);
+            let followup_input = transform(input, &transformation);
+            let followup_output = system(&followup_input);
+            match holds(output, &followup_output) {
+                true => None,
+                false => Some((followup_input, followup_output)),
+            }
+        }));
+
+        Metamorphic { relations, ..self }
+    }
+
+    /// Checks every relation, panicking with a description of the shrunk
+    /// source input, transformation and both outputs if one does not hold.
+    pub fn property(&self) {
+        self.property_cfg(&DEFAULT_CONFIG);
+    }
+
+    pub fn property_cfg(&self, cfg: &Config) {
+        property_cfg(
+            |maat| {
+                let input = maat.generate("input", &*self.input);
+                let output = (self.system)(&input);
+                for relation in &self.relations {
+                    if let Some((followup_input, followup_output)) = relation(maat, &input, &output)
+                    {
+                        maat.note("source_output", output);
+                        maat.note("followup_input", followup_input);
+                        maat.note("followup_output", followup_output);
+                        return false;
+                    }
+                }
+
+                true
+            },
+            cfg,
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generators::i64;
+
+    #[test]
+    pub fn roundtrip_holds() {
+        roundtrip(i64(-1000, 1000), |x| x.to_string(), |s| s.parse::<i64>());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "[maat] Noted by the property:\nencoded: alloc::string::String = \"-1000\""
+    )]
+    pub fn roundtrip_reports_encoding() {
+        roundtrip(
+            i64(-1000, 1000),
+            |x| x.to_string(),
+            |s| s.trim_start_matches('-').parse::<i64>(),
+        );
+    }
+
+    #[test]
+    pub fn differential_agrees() {
+        differential(i64(-1000, 1000), |x| x.abs(), |x| (x * x).isqrt());
+    }
+
+    #[test]
+    #[should_panic(expected = "input: i64 = 100\n\n[maat] Noted by the property:\n\
+        reference: i64 = 100\ncandidate: i64 = 99")]
+    pub fn differential_reports_both_outputs() {
+        differential(i64(0, 1000), |x| *x.min(&100), |x| *x.min(&99));
+    }
+
+    #[test]
+    #[should_panic(expected = "candidate: alloc::string::String = \"panicked: too big\"")]
+    pub fn differential_compares_panics() {
+        differential_by(
+            i64(0, 1000),
+            |x| x * 2,
+            |x| if *x > 500 { panic!("too big") } else { x * 2 },
+            |a, b| a == b,
+        );
+    }
+
+    #[test]
+    pub fn metamorphic_relations_hold() {
+        Metamorphic::new(i64(-1000, 1000), |x| x.abs())
+            .relation("negate", i64(0, 1), |x, _| -x, |a, b| a == b)
+            .relation(
+                "grow",
+                i64(1, 100),
+                |x, by| if *x < 0 { x - by } else { x + by },
+                |a, b| b > a,
+            )
+            .property();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "input: i64 = 100\nnegate: i64 = 0\n\n[maat] Noted by the property:\n\
+        source_output: i64 = 100\nfollowup_input: i64 = -100\nfollowup_output: i64 = -100\n"
+    )]
+    pub fn metamorphic_reports_transformation() {
+        let abs = |x: &i64| if x.abs() < 100 { x.abs() } else { *x };
+        Metamorphic::new(i64(0, 1000), abs)
+            .relation("negate", i64(0, 1), |x, _| -x, |a, b| a == b)
+            .property();
+    }
+}
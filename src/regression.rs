@@ -0,0 +1,545 @@
+//! Generation of regression tests from counterexamples.
+//!
+//! Values generated with [crate::Maat::generate_serializable] are written
+//! out as JSON. Other values are written out using their [Debug]
+//! representation, which for most types is also valid Rust. The type of
+//! each value is used to fix up the common cases where it is not, such as
+//! `Vec` and `String`. Values which still aren't valid Rust are written as a
+//! `compile_error!`, to be filled in by hand.
+//!
+//! Types from outside `std` are written without their path, as they are in
+//! their [Debug] representation, so they must be in scope where the test is
+//! written (as they usually are beside the property).
+
+use std::fmt::{Debug, Write};
+
+use crate::{Failure, NamedValue};
+
+impl Failure {
+    /// Returns the source of a `#[test]` which replays the shrunk values
+    /// through the test function `test_name` (see [crate::with_replay]),
+    /// so it belongs in the same module as that function.
+    pub fn regression_test(&self, test_name: &str) -> String {
+        let mut result = String::new();
+        writeln!(result, "#[test]").unwrap();
+        writeln!(result, "fn {test_name}_regression() {{").unwrap();
+        match self.replay().and_then(|replay| replay.to_json()) {
+            Some(json) => {
+                let hashes = raw_string_hashes(&json);
+                writeln!(
+                    result,
+                    "    let replay = maat::Replay::from_json(r{hashes}\"{json}\"{hashes}).unwrap();"
+                )
+                .unwrap();
+            }
+            None => {
+                write!(result, "    let replay = maat::Replay::new()").unwrap();
+                for value in &self.shrunk {
+                    write!(result, "\n        {}", replay_value(value)).unwrap();
+                }
+
+                writeln!(result, ";").unwrap();
+            }
+        }
+        writeln!(result, "    maat::with_replay(&replay, {test_name});").unwrap();
+        writeln!(result, "}}").unwrap();
+        result
+    }
+}
+
+fn replay_value(value: &NamedValue) -> String {
+    let ty = Type::parse(value.type_name());
+    let debug = format!("{:?}", value.value());
+    // a value which can't be written fails to compile, rather than being
+    // left out, which would misalign the rest of the replay
+    let expr = Value::parse(&debug)
+        .and_then(|parsed| rust_expr(&ty, &parsed))
+        .unwrap_or_else(|| {
+            let message = format!("TODO: `{debug}` is not valid Rust, so write it by hand");
+            format!("compile_error!({message:?})")
+        });
+
+    format!(".value::<{}>({:?}, {expr})", ty.source, value.name())
+}
+
+/// The number of `#`s needed to write `text` in a raw string literal.
+fn raw_string_hashes(text: &str) -> String {
+    let mut hashes = String::new();
+    while text.contains(&format!("\"{hashes}")) {
+        hashes.push('#');
+    }
+
+    hashes
+}
+
+/// A parsed [std::any::type_name].
+struct Type {
+    /// The type as it should be written in source.
+    source: String,
+    /// The final segment of the path, without generics.
+    base: String,
+    args: Vec<Type>,
+    kind: TypeKind,
+}
+
+enum TypeKind {
+    Path,
+    Tuple,
+    Array,
+    Other,
+}
+
+impl Type {
+    fn parse(type_name: &str) -> Type {
+        let mut input = type_name.trim();
+        Type::parse_from(&mut input)
+    }
+
+    fn parse_from(input: &mut &str) -> Type {
+        *input = input.trim_start();
+        if let Some(rest) = input.strip_prefix('(') {
+            *input = rest;
+            let args = Type::parse_list(input, ')');
+            let source = match args.len() {
+                1 => format!("({},)", args[0].source),
+                _ => format!("({})", join(args.iter().map(|a| a.source.as_str()))),
+            };
+
+            return Type {
+                source,
+                base: String::new(),
+                args,
+                kind: TypeKind::Tuple,
+            };
+        }
+
+        if let Some(rest) = input.strip_prefix('[') {
+            *input = rest;
+            let element = Type::parse_from(input);
+            let end = input.find(']').unwrap_or(input.len());
+            let length = input[..end].trim_start_matches([';', ' ']).to_string();
+            *input = input.get(end + 1..).unwrap_or("");
+            return Type {
+                source: format!("[{}; {length}]", element.source),
+                base: String::new(),
+                args: vec![element],
+                kind: TypeKind::Array,
+            };
+        }
+
+        let end = input
+            .find(['<', '>', ',', ')', ']', ';'])
+            .unwrap_or(input.len());
+        let path = input[..end].trim();
+        *input = &input[end..];
+        let args = match input.strip_prefix('<') {
+            Some(rest) => {
+                *input = rest;
+                Type::parse_list(input, '>')
+            }
+            None => Vec::new(),
+        };
+
+        let path = short_path(path);
+        let source = if args.is_empty() {
+            path.clone()
+        } else {
+            format!("{path}<{}>", join(args.iter().map(|a| a.source.as_str())))
+        };
+
+        Type {
+            base: path.rsplit("::").next().unwrap_or_default().to_string(),
+            kind: if path.starts_with(['&', '*']) || path.contains(' ') {
+                TypeKind::Other
+            } else {
+                TypeKind::Path
+            },
+            source,
+            args,
+        }
+    }
+
+    fn parse_list(input: &mut &str, close: char) -> Vec<Type> {
+        let mut args = Vec::new();
+        loop {
+            *input = input.trim_start();
+            if let Some(rest) = input.strip_prefix(close) {
+                *input = rest;
+                return args;
+            }
+
+            if input.is_empty() {
+                return args;
+            }
+
+            args.push(Type::parse_from(input));
+            *input = input.trim_start();
+            if let Some(rest) = input.strip_prefix(',') {
+                *input = rest;
+            }
+        }
+    }
+}
+
+/// Shortens the paths of common types, which are
+/// reported under their defining (rather than public) modules.
+fn short_path(path: &str) -> String {
+    for prelude in [
+        "alloc::string::String",
+        "alloc::vec::Vec",
+        "alloc::boxed::Box",
+        "core::option::Option",
+        "core::result::Result",
+    ] {
+        if path == prelude {
+            return prelude.rsplit("::").next().unwrap().to_string();
+        }
+    }
+
+    for prefix in ["alloc::", "core::"] {
+        if let Some(rest) = path.strip_prefix(prefix) {
+            return format!("std::{rest}");
+        }
+    }
+
+    match path.starts_with("std::") {
+        true => path.to_string(),
+        false => path.rsplit("::").next().unwrap_or(path).to_string(),
+    }
+}
+
+/// A parsed [Debug] representation.
+#[derive(Debug)]
+enum Value {
+    /// Anything not otherwise understood: numbers, identifiers, etc.
+    Atom(String),
+    /// A string or character literal, including its quotes.
+    Literal(String),
+    /// A bracketed list, optionally preceded by a name
+    /// (as in `Some(1)` or `Point { x: 1 }`).
+    Group {
+        name: String,
+        open: char,
+        items: Vec<Value>,
+        source: String,
+    },
+}
+
+impl Value {
+    fn parse(debug: &str) -> Option<Value> {
+        let mut input = debug.trim();
+        let value = Value::parse_from(&mut input)?;
+        input.trim().is_empty().then_some(value)
+    }
+
+    fn parse_from(input: &mut &str) -> Option<Value> {
+        *input = input.trim_start();
+        let start = *input;
+        if input.starts_with(['"', '\'']) {
+            let end = literal_end(input)?;
+            let literal = input[..end].to_string();
+            *input = &input[end..];
+            return Some(Value::Literal(literal));
+        }
+
+        let end = input
+            .find(|c: char| "[](){},\"'".contains(c))
+            .unwrap_or(input.len());
+        let name = input[..end].trim().to_string();
+        *input = &input[end..];
+
+        let Some(open) = input.chars().next().filter(|c| "[({".contains(*c)) else {
+            return (!name.is_empty()).then_some(Value::Atom(name));
+        };
+
+        let close = match open {
+            '[' => ']',
+            '(' => ')',
+            _ => '}',
+        };
+
+        *input = &input[1..];
+        let mut items = Vec::new();
+        loop {
+            *input = input.trim_start();
+            if let Some(rest) = input.strip_prefix(close) {
+                *input = rest;
+                break;
+            }
+
+            items.push(Value::parse_from(input)?);
+            *input = input.trim_start();
+            if let Some(rest) = input.strip_prefix(',') {
+                *input = rest;
+            } else if !input.starts_with(close) {
+                return None;
+            }
+        }
+
+        let source = start[..start.len() - input.len()].to_string();
+        Some(Value::Group {
+            name,
+            open,
+            items,
+            source,
+        })
+    }
+
+    fn source(&self) -> &str {
+        match self {
+            Value::Atom(source) | Value::Literal(source) | Value::Group { source, .. } => source,
+        }
+    }
+}
+
+/// Finds the end of the string or character literal at the start of `input`.
+fn literal_end(input: &str) -> Option<usize> {
+    let quote = input.chars().next()?;
+    let mut escaped = false;
+    for (ix, c) in input.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return Some(ix + 1),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Converts a [Debug] representation into a Rust expression of the given
+/// type, if it can be written as one.
+fn rust_expr(ty: &Type, value: &Value) -> Option<String> {
+    let expr = match (&ty.kind, ty.base.as_str(), value) {
+        (TypeKind::Path, "String", Value::Literal(literal)) => format!("String::from({literal})"),
+        (
+            TypeKind::Path,
+            "Vec",
+            Value::Group {
+                name,
+                open: '[',
+                items,
+                ..
+            },
+        ) if name.is_empty() => {
+            format!("vec![{}]", rust_exprs(ty.args.first(), items)?)
+        }
+        (
+            TypeKind::Array,
+            _,
+            Value::Group {
+                name,
+                open: '[',
+                items,
+                ..
+            },
+        ) if name.is_empty() => {
+            format!("[{}]", rust_exprs(ty.args.first(), items)?)
+        }
+        (
+            TypeKind::Path,
+            "Option",
+            Value::Group {
+                name,
+                open: '(',
+                items,
+                ..
+            },
+        ) if items.len() == 1 => {
+            format!("{name}({})", rust_exprs(ty.args.first(), items)?)
+        }
+        (TypeKind::Path, "Box", value) => {
+            format!("Box::new({})", rust_exprs(ty.args.first(), [value])?)
+        }
+        (
+            TypeKind::Path,
+            "Result",
+            Value::Group {
+                name,
+                open: '(',
+                items,
+                ..
+            },
+        ) if items.len() == 1 => {
+            let arg = if name == "Ok" {
+                ty.args.first()
+            } else {
+                ty.args.get(1)
+            };
+            format!("{name}({})", rust_exprs(arg, items)?)
+        }
+        (
+            TypeKind::Tuple,
+            _,
+            Value::Group {
+                name,
+                open: '(',
+                items,
+                ..
+            },
+        ) if name.is_empty() && items.len() == ty.args.len() => {
+            let items = items
+                .iter()
+                .zip(&ty.args)
+                .map(|(v, t)| rust_expr(t, v))
+                .collect::<Option<Vec<_>>>()?;
+            if ty.args.len() == 1 {
+                format!("({},)", join(items.iter()))
+            } else {
+                format!("({})", join(items.iter()))
+            }
+        }
+        (TypeKind::Path, "f32" | "f64", Value::Atom(atom)) => match atom.as_str() {
+            "NaN" => format!("{}::NAN", ty.base),
+            "inf" => format!("{}::INFINITY", ty.base),
+            "-inf" => format!("{}::NEG_INFINITY", ty.base),
+            _ => atom.clone(),
+        },
+        (TypeKind::Other, _, Value::Literal(literal)) if ty.source == "&str" => literal.clone(),
+        _ if is_rust(value) => value.source().to_string(),
+        _ => return None,
+    };
+
+    Some(expr)
+}
+
+fn rust_exprs<'v>(
+    ty: Option<&Type>,
+    values: impl IntoIterator<Item = &'v Value>,
+) -> Option<String> {
+    let exprs = values
+        .into_iter()
+        .map(|value| match ty {
+            Some(ty) => rust_expr(ty, value),
+            None => is_rust(value).then(|| value.source().to_string()),
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(join(exprs.iter()))
+}
+
+/// Whether a [Debug] representation, of a type which isn't known, is also
+/// a Rust expression. This is conservative: string literals might need to
+/// be `String`s, and braces without a name are maps or sets.
+fn is_rust(value: &Value) -> bool {
+    match value {
+        Value::Atom(atom) => is_rust_atom(atom),
+        Value::Literal(literal) => literal.starts_with('\''),
+        Value::Group {
+            name, open, items, ..
+        } => {
+            let named = match name.is_empty() {
+                true => *open != '{',
+                false => is_rust_atom(name),
+            };
+
+            named && items.iter().all(is_rust)
+        }
+    }
+}
+
+/// Whether an atom is a number or a path,
+/// possibly preceded by a field name.
+fn is_rust_atom(atom: &str) -> bool {
+    let is_ident = |s: &str| {
+        s.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+
+    let atom = match atom.split_once(':') {
+        Some((field, rest)) if is_ident(field.trim()) && !rest.starts_with(':') => rest.trim(),
+        _ => atom.trim(),
+    };
+
+    let number = atom.strip_prefix('-').unwrap_or(atom);
+    if number.starts_with(|c: char| c.is_ascii_digit()) {
+        return number.parse::<f64>().is_ok();
+    }
+
+    !matches!(atom, "NaN" | "inf") && atom.split("::").all(is_ident)
+}
+
+fn join<S: AsRef<str>>(items: impl Iterator<Item = S>) -> String {
+    let mut result = String::new();
+    for (ix, item) in items.enumerate() {
+        if ix > 0 {
+            result.push_str(", ");
+        }
+
+        result.push_str(item.as_ref());
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, generators::duration};
+    use std::time::Duration;
+
+    fn expr(type_name: &str, debug: &str) -> Option<String> {
+        Value::parse(debug).and_then(|value| rust_expr(&Type::parse(type_name), &value))
+    }
+
+    #[test]
+    pub fn nested_collections() {
+        assert_eq!(
+            expr(
+                "alloc::vec::Vec<core::option::Option<alloc::string::String>>",
+                r#"[Some("a, \"b\""), None]"#
+            )
+            .unwrap(),
+            r#"vec![Some(String::from("a, \"b\"")), None]"#
+        );
+
+        assert_eq!(
+            expr("(i64, alloc::vec::Vec<[u8; 2]>)", "(1, [[2, 3]])").unwrap(),
+            "(1, vec![[2, 3]])"
+        );
+    }
+
+    #[test]
+    pub fn skips_values_which_are_not_rust() {
+        assert_eq!(expr("core::time::Duration", "1.5s"), None);
+        let map = "std::collections::hash::map::HashMap<i64, i64>";
+        assert_eq!(expr(map, "{1: 2}"), None);
+        let point = r#"Point { x: 1, label: "a" }"#;
+        assert_eq!(expr("my_crate::Point", point), None);
+        assert_eq!(
+            expr("my_crate::Point", "Point { x: -1.5, y: Some('a') }").unwrap(),
+            "Point { x: -1.5, y: Some('a') }"
+        );
+    }
+
+    #[test]
+    pub fn type_names() {
+        assert_eq!(
+            Type::parse("alloc::vec::Vec<(alloc::string::String, core::time::Duration)>").source,
+            "Vec<(String, std::time::Duration)>"
+        );
+        assert_eq!(
+            Type::parse("my_crate::shapes::Point<i64>").source,
+            "Point<i64>"
+        );
+    }
+
+    #[test]
+    pub fn marks_values_which_are_not_rust() {
+        let failure = check(|maat| {
+            let retries = maat.generate("retries", crate::generators::u8(0, 10));
+            let timeout = maat.generate("timeout", duration(..));
+            timeout < Duration::from_secs(1) || retries < 3
+        })
+        .unwrap_err();
+
+        let test = failure.regression_test("timeouts");
+        assert!(test.contains(
+            "\n        .value::<u8>(\"retries\", 3)\n        \
+            .value::<std::time::Duration>(\"timeout\", compile_error!(\"TODO: `1s` is not valid Rust, \
+            so write it by hand\"));"
+        ));
+        assert!(test.contains("maat::with_replay(&replay, timeouts);"));
+    }
+}
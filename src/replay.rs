@@ -0,0 +1,307 @@
+use std::{
+    any::type_name,
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use dynamic::Dynamic;
+use serde_json::json;
+
+use crate::{report, Config, Failure, JsonCodec, Maat, Mode, NamedValue, Stats, DEFAULT_CONFIG};
+
+/// A set of values to feed back through a property, in place of
+/// generating new ones. This can be used to rerun a counterexample
+/// under a debugger, or with extra logging.
+///
+/// Values can either be given directly with [Replay::value], or read
+/// from JSON with [Replay::from_json]. Only values generated with
+/// [Maat::generate_serializable] can be replayed from JSON.
+///
+/// The JSON format is an array of `{ "name", "type_name", "value" }` objects,
+/// which is the same as the `shrunk` values in the JSON output. It may also
+/// be an object `{ "property", "values" }`, to replay the values only for
+/// the property with that name (see [Config::name]).
+#[derive(Debug, Clone, Default)]
+pub struct Replay {
+    property: Option<String>,
+    values: Vec<ReplayValue>,
+}
+
+#[derive(Debug, Clone)]
+struct ReplayValue {
+    name: String,
+    type_name: String,
+    value: ReplayData,
+}
+
+#[derive(Debug, Clone)]
+enum ReplayData {
+    Json(serde_json::Value),
+    Value(Rc<Dynamic>),
+}
+
+impl Replay {
+    pub fn new() -> Replay {
+        Replay::default()
+    }
+
+    /// Adds a value to be replayed. Values must be added
+    /// in the order that the property generates them.
+    pub fn value<T: 'static>(mut self, name: &str, value: T) -> Replay {
+        self.values.push(ReplayValue {
+            name: name.to_string(),
+            type_name: type_name::<T>().to_string(),
+            value: ReplayData::Value(Rc::from(Dynamic::new(value))),
+        });
+
+        self
+    }
+
+    /// Sets the name of the property that the values were recorded from.
+    /// When read from `MAAT_REPLAY`, the values are then only replayed for
+    /// that property, and other properties run as usual.
+    pub fn property(self, name: &str) -> Replay {
+        Replay {
+            property: Some(name.to_string()),
+            ..self
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Replay, String> {
+        let parsed: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let (property, values) = match &parsed {
+            serde_json::Value::Object(object) => (
+                Some(
+                    object
+                        .get("property")
+                        .and_then(|p| p.as_str())
+                        .ok_or("expected a string `property`")?
+                        .to_string(),
+                ),
+                object.get("values").unwrap_or(&serde_json::Value::Null),
+            ),
+            values => (None, values),
+        };
+
+        let values = values
+            .as_array()
+            .ok_or("expected an array of values")?
+            .iter()
+            .map(|value| {
+                let field = |field: &str| {
+                    value[field]
+                        .as_str()
+                        .map(str::to_string)
+                        .ok_or(format!("expected a string `{field}` in {value}"))
+                };
+
+                Ok(ReplayValue {
+                    name: field("name")?,
+                    type_name: field("type_name")?,
+                    value: ReplayData::Json(value["value"].clone()),
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Replay { property, values })
+    }
+
+    /// Returns the JSON form of the values,
+    /// if they were all read from JSON.
+    pub fn to_json(&self) -> Option<String> {
+        let values = self
+            .values
+            .iter()
+            .map(|value| match &value.value {
+                ReplayData::Json(json) => Some(json!({
+                    "name": value.name,
+                    "type_name": value.type_name,
+                    "value": json,
+                })),
+                ReplayData::Value(_) => None,
+            })
+            .collect::<Option<_>>()?;
+
+        let values = serde_json::Value::Array(values);
+        match &self.property {
+            Some(property) => Some(json!({ "property": property, "values": values }).to_string()),
+            None => Some(values.to_string()),
+        }
+    }
+
+    /// The replay for the named property: the one set by [with_replay],
+    /// or else the one read from the `MAAT_REPLAY` environment variable
+    /// (see [Replay::from_var]).
+    pub(crate) fn current(property: &str) -> Option<Replay> {
+        if let Some(replay) = CURRENT.with(|current| current.borrow().clone()) {
+            return Some(replay);
+        }
+
+        Replay::from_var(std::env::var("MAAT_REPLAY").ok()?, property)
+    }
+
+    /// Reads a replay from the value of `MAAT_REPLAY`, which may contain
+    /// either the JSON itself or the path to a file containing it. Returns
+    /// `None` if the values were recorded from a different property.
+    pub(crate) fn from_var(var: String, property: &str) -> Option<Replay> {
+        let json = if var.trim_start().starts_with(['[', '{']) {
+            var
+        } else {
+            std::fs::read_to_string(&var)
+                .unwrap_or_else(|e| panic!("[maat] Unable to read MAAT_REPLAY file {var}: {e}"))
+        };
+
+        match Replay::from_json(&json) {
+            Ok(replay) if replay.property.as_ref().is_some_and(|p| p != property) => None,
+            Ok(replay) => Some(replay),
+            Err(e) => panic!("[maat] Unable to parse MAAT_REPLAY: {e}"),
+        }
+    }
+
+    /// Returns the value to use for the `ix`th generated value,
+    /// checking that it is the value that the test expects.
+    pub(crate) fn get<T>(
+        &self,
+        ix: usize,
+        name: &'static str,
+        json: Option<JsonCodec<T>>,
+    ) -> NamedValue
+    where
+        T: Clone + std::fmt::Debug + 'static,
+    {
+        let existing = self.values.get(ix).unwrap_or_else(|| {
+            panic!(
+                "[maat] Usage error: while replaying, value {ix} ({name}) was generated but only {} values were recorded",
+                self.values.len()
+            )
+        });
+
+        if existing.name != name {
+            panic!(
+                "[maat] Usage error: while replaying, got a different name for value {ix}: was {}, is {}",
+                existing.name, name
+            );
+        }
+
+        let old = &existing.type_name;
+        let new = type_name::<T>();
+        let (value, json) = match &existing.value {
+            ReplayData::Value(value) => match value.downcast_ref::<T>() {
+                Some(value) => (value.clone(), json.and_then(|json| (json.to_json)(value))),
+                None => panic!("[maat] Usage error: while replaying, got a different type for value {ix} ({name}): was {old}, is {new}"),
+            },
+            ReplayData::Json(existing) => {
+                let Some(json) = json else {
+                    panic!("[maat] Usage error: while replaying, value {ix} ({name}) was not generated with generate_serializable");
+                };
+
+                match (json.from_json)(existing) {
+                    Some(value) => (value, Some(existing.clone())),
+                    None => panic!("[maat] Usage error: while replaying, unable to read value {ix} ({name}): was {old}, is {new}"),
+                }
+            }
+        };
+
+        NamedValue {
+            name,
+            type_name: new,
+            value: Box::new(value),
+            json,
+        }
+    }
+}
+
+impl Failure {
+    /// Returns the shrunk values in a form that can be replayed,
+    /// if they were all generated with [Maat::generate_serializable].
+    pub fn replay(&self) -> Option<Replay> {
+        let values = self
+            .shrunk
+            .iter()
+            .map(|value| {
+                Some(ReplayValue {
+                    name: value.name().to_string(),
+                    type_name: value.type_name().to_string(),
+                    value: ReplayData::Json(value.json()?.clone()),
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Replay {
+            property: None,
+            values,
+        })
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Replay>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, with every property that it checks on this thread running
+/// once with the given values rather than generating new ones. This is
+/// how a regression test (see [Failure::regression_test]) replays a
+/// counterexample through an existing test.
+pub fn with_replay<R>(replay: &Replay, f: impl FnOnce() -> R) -> R {
+    struct Reset(Option<Replay>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+        }
+    }
+
+    let previous = CURRENT.with(|current| current.borrow_mut().replace(replay.clone()));
+    let _reset = Reset(previous);
+    f()
+}
+
+/// Runs the property once with the given values,
+/// panicking if it is falsified.
+pub fn replay(test: impl Fn(&mut Maat) -> bool, replay: &Replay) {
+    if let Err(failure) = check_replay(test, replay) {
+        panic!("{failure}");
+    }
+}
+
+/// Runs the property once with the given values,
+/// returning the values if it is falsified.
+pub fn check_replay(test: impl Fn(&mut Maat) -> bool, replay: &Replay) -> Result<Stats, Failure> {
+    run_replay(test, replay, &DEFAULT_CONFIG)
+}
+
+pub(crate) fn run_replay(
+    test: impl Fn(&mut Maat) -> bool,
+    replay: &Replay,
+    cfg: &Config,
+) -> Result<Stats, Failure> {
+    let start = Instant::now();
+    let mut record = Vec::new();
+    let mode = Mode::Replaying {
+        replay,
+        replay_ix: 0,
+        record: &mut record,
+    };
+
+    if test(&mut Maat::new(mode)) {
+        return Ok(Stats {
+            seed: None,
+            iterations: 1,
+            elapsed: start.elapsed(),
+            labels: Vec::new(),
+        });
+    }
+
+    let values = report::display_values(&record, cfg);
+    Err(Failure {
+        seed: None,
+        iteration: 0,
+        shrink_steps: 0,
+        shrink_time: Duration::ZERO,
+        original: Vec::new(),
+        message: format!("\n[maat] Falsified property with replayed values:\n{values}\n"),
+        shrunk: record,
+        buffer: None,
+    })
+}
@@ -0,0 +1,280 @@
+use std::{
+    any::Any,
+    fmt::{Debug, Display, Write as _},
+    io::Write,
+    path::Path,
+    time::Duration,
+};
+
+use serde_json::json;
+
+use crate::Config;
+
+/// Summary of a property that held for every iteration.
+#[derive(Debug, Clone)]
+pub struct Stats {
+    /// The seed used to initialize the RNG,
+    /// or `None` if the values were replayed.
+    pub seed: Option<u64>,
+    /// The number of iterations that were run.
+    pub iterations: usize,
+    /// The total time spent running iterations.
+    pub elapsed: Duration,
+    /// The number of iterations with each label (see [crate::Maat::label]),
+    /// most common first.
+    pub labels: Vec<(String, usize)>,
+}
+
+impl Stats {
+    pub fn iterations_per_sec(&self) -> f64 {
+        self.iterations as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// A table of the percentage of iterations with each label.
+    pub fn distribution(&self) -> String {
+        let mut result = String::new();
+        for (label, count) in &self.labels {
+            let percentage = crate::coverage::percentage(*count, self.iterations);
+            writeln!(result, "{percentage:5.1}% {label}").unwrap();
+        }
+
+        result
+    }
+}
+
+/// A counterexample to a property, as found by [crate::check].
+#[derive(Debug)]
+pub struct Failure {
+    /// The seed used to initialize the RNG; running again with
+    /// the same seed will reproduce the failure.
+    /// This is `None` if the values were replayed
+    /// or generated from an input buffer.
+    pub seed: Option<u64>,
+    /// The (zero-based) iteration at which the failure was found.
+    pub iteration: usize,
+    /// The number of successful shrinks applied to the original values.
+    pub shrink_steps: usize,
+    /// How long shrinking took.
+    pub shrink_time: Duration,
+    /// The values as they were when the failure was first found.
+    pub original: Vec<NamedValue>,
+    /// The values after shrinking.
+    pub shrunk: Vec<NamedValue>,
+    /// The shrunk input buffer, if the property was run
+    /// against one (see [crate::Config::input]).
+    pub buffer: Option<Box<[u8]>>,
+    /// The human-readable description of the failure,
+    /// as used by [crate::property].
+    pub message: String,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// A single value from a recording, with the name it was generated under.
+pub struct NamedValue {
+    pub(crate) name: &'static str,
+    pub(crate) type_name: &'static str,
+    pub(crate) value: Box<dyn DebugAny>,
+    pub(crate) json: Option<serde_json::Value>,
+}
+
+impl NamedValue {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Returns the value, if it has type `T`.
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.value.as_any().downcast_ref()
+    }
+
+    /// Returns the value for formatting.
+    pub fn value(&self) -> &dyn Debug {
+        self.value.as_debug()
+    }
+
+    /// Returns the value as JSON, if it was generated with
+    /// [crate::Maat::generate_serializable].
+    pub fn json(&self) -> Option<&serde_json::Value> {
+        self.json.as_ref()
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "type_name": self.type_name,
+            "value": self.json,
+            "debug": format!("{:?}", self.value.as_debug()),
+        })
+    }
+}
+
+impl Debug for NamedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NamedValue")
+            .field("name", &self.name)
+            .field("type_name", &self.type_name)
+            .field("value", &self.value.as_debug())
+            .finish()
+    }
+}
+
+/// Builds the JSON record for a single run of a property.
+pub(crate) fn json_record(
+    name: &str,
+    elapsed: Duration,
+    result: &Result<Stats, Failure>,
+) -> serde_json::Value {
+    // the rate of iterations excludes the time spent shrinking
+    let (seed, iterations, elapsed) = match result {
+        Ok(stats) => (stats.seed, stats.iterations, elapsed),
+        Err(failure) => (
+            failure.seed,
+            failure.iteration + 1,
+            elapsed.saturating_sub(failure.shrink_time),
+        ),
+    };
+
+    let mut record = json!({
+        "name": name,
+        "seed": seed,
+        "iterations": iterations,
+        "iterations_per_sec": iterations as f64 / elapsed.as_secs_f64(),
+        "passed": result.is_ok(),
+    });
+
+    if let Ok(stats) = result {
+        if !stats.labels.is_empty() {
+            let labels = stats.labels.iter().map(|(l, c)| (l.clone(), json!(c)));
+            record["labels"] = serde_json::Value::Object(labels.collect());
+        }
+    }
+
+    if let Err(failure) = result {
+        let values = |values: &[NamedValue]| values.iter().map(NamedValue::to_json).collect();
+        record["shrink_steps"] = failure.shrink_steps.into();
+        record["shrink_secs"] = failure.shrink_time.as_secs_f64().into();
+        record["original"] = serde_json::Value::Array(values(&failure.original));
+        record["shrunk"] = serde_json::Value::Array(values(&failure.shrunk));
+    }
+
+    record
+}
+
+/// Writes the output, either appending it to
+/// the file at `path` or to stdout if `path` is `-`.
+pub(crate) fn write_output(path: &Path, output: &str) {
+    let result = if path == Path::new("-") {
+        std::io::stdout().write_all(output.as_bytes())
+    } else {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(output.as_bytes()))
+    };
+
+    if let Err(e) = result {
+        eprintln!("[maat] Unable to write output to {}: {e}", path.display());
+    }
+}
+
+pub(crate) fn display_values(values: &[NamedValue], cfg: &Config) -> String {
+    let mut result = String::new();
+    for value in values {
+        let type_name = if cfg.short_type_names {
+            short_type_name(value.type_name)
+        } else {
+            value.type_name.to_string()
+        };
+
+        let mut formatted = (cfg.formatter)(value.value());
+        if let Some(max_length) = cfg.max_value_length {
+            truncate(&mut formatted, max_length);
+        }
+
+        writeln!(result, "{}: {type_name} = {formatted}", value.name).unwrap();
+    }
+
+    result
+}
+
+/// Removes the module paths from all the types within a type name.
+fn short_type_name(type_name: &str) -> String {
+    let mut result = String::new();
+    let mut path = String::new();
+    for c in type_name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+        } else {
+            result.push_str(path.rsplit("::").next().unwrap_or_default());
+            path.clear();
+            result.push(c);
+        }
+    }
+
+    result.push_str(path.rsplit("::").next().unwrap_or_default());
+    result
+}
+
+fn truncate(value: &mut String, max_length: usize) {
+    let length = value.chars().count();
+    if length > max_length {
+        let end = value
+            .char_indices()
+            .nth(max_length)
+            .map_or(value.len(), |(ix, _)| ix);
+        value.truncate(end);
+        write!(value, "… ({} more characters)", length - max_length).unwrap();
+    }
+}
+
+/// DebugAny exists so that a [NamedValue] can be
+/// both printed and downcast to its original type.
+pub(crate) trait DebugAny {
+    fn as_any(&self) -> &dyn Any;
+    fn as_debug(&self) -> &dyn Debug;
+}
+
+impl<T> DebugAny for T
+where
+    T: Any + Debug,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_debug(&self) -> &dyn Debug {
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn short_type_names() {
+        assert_eq!(
+            short_type_name("alloc::vec::Vec<(alloc::string::String, &core::time::Duration)>"),
+            "Vec<(String, &Duration)>"
+        );
+    }
+
+    #[test]
+    pub fn truncation() {
+        let mut value = "αβγδε".to_string();
+        truncate(&mut value, 2);
+        assert_eq!(value, "αβ… (3 more characters)");
+    }
+}
@@ -0,0 +1,211 @@
+//! Choosing which inputs to test.
+//!
+//! In the guided [Search] modes, each iteration records the choices
+//! consumed by its generators (see [crate::choices]). Choice sequences
+//! that did something interesting are kept in a corpus, and later
+//! iterations are mostly made by mutating entries of the corpus rather
+//! than by starting from scratch:
+//! - [Search::CoverageGuided] keeps every iteration which makes a new
+//!   [Maat::observe] observation.
+//! - [Search::Targeted] keeps, for each [Maat::target] label, the iteration
+//!   with the highest value so far, so mutating it climbs towards a maximum.
+
+use std::{collections::HashSet, time::Instant};
+
+use rand::{Rng, RngCore, SeedableRng};
+
+use crate::{
+    choices::ChoiceRng, coverage::Coverage, handle_failure, Config, Failure, Maat, Mode,
+    Observations, Stats, RNG,
+};
+
+/// How [crate::check_cfg] chooses the inputs for each iteration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Search {
+    /// Every iteration is generated independently.
+    #[default]
+    Random,
+    /// Iterations which reach new features (see [Maat::observe])
+    /// are kept and mutated to produce further iterations.
+    CoverageGuided,
+    /// Iterations are mutated to maximize the metrics
+    /// reported by [Maat::target].
+    Targeted,
+}
+
+/// The state of a guided search.
+#[derive(Default)]
+struct Corpus {
+    entries: Vec<Vec<u64>>,
+    /// Features observed so far, for [Search::CoverageGuided].
+    seen: HashSet<u64>,
+    /// The highest value of each target so far and the index of
+    /// the entry which reached it, for [Search::Targeted].
+    best: Vec<(&'static str, f64, usize)>,
+}
+
+impl Corpus {
+    fn add(&mut self, search: Search, choices: Vec<u64>, observations: Observations) {
+        match search {
+            Search::Random => {}
+            Search::CoverageGuided => {
+                let mut interesting = false;
+                for feature in observations.features {
+                    interesting |= self.seen.insert(feature);
+                }
+
+                if interesting {
+                    self.entries.push(choices);
+                }
+            }
+            Search::Targeted => {
+                for (label, value) in observations.targets {
+                    if value.is_nan() {
+                        continue;
+                    }
+
+                    match self.best.iter_mut().find(|(l, _, _)| *l == label) {
+                        Some((_, best, ix)) => {
+                            if value > *best {
+                                *best = value;
+                                self.entries[*ix] = choices.clone();
+                            }
+                        }
+                        None => {
+                            self.best.push((label, value, self.entries.len()));
+                            self.entries.push(choices.clone());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn run_guided(
+    test: impl Fn(&mut Maat) -> bool,
+    seed: u64,
+    cfg: &Config,
+) -> Result<Stats, Failure> {
+    let mut rng = RNG::seed_from_u64(seed);
+    let start = Instant::now();
+
+    let mut corpus = Corpus::default();
+    let mut coverage = Coverage::default();
+    for iteration in 0..cfg.iterations {
+        let choices = if corpus.entries.is_empty() || rng.gen_ratio(1, 8) {
+            Vec::new()
+        } else {
+            mutate(&corpus.entries, &mut rng)
+        };
+
+        let mut choice_rng = ChoiceRng::new(choices, RNG::seed_from_u64(rng.next_u64()));
+
+        // store RNG state so we can reuse it for recording, if needed
+        let iteration_rng = choice_rng.clone();
+        let mut maat = Maat::new(Mode::Testing {
+            rng: &mut choice_rng,
+        });
+
+        let passed = test(&mut maat);
+        let observations = maat.observations;
+        if !passed {
+            return Err(handle_failure(
+                test,
+                iteration_rng,
+                Some(seed),
+                iteration,
+                cfg,
+            ));
+        }
+
+        coverage.add(&observations);
+        corpus.add(cfg.search, choice_rng.into_choices(), observations);
+    }
+
+    coverage.finish(seed, cfg.iterations, start.elapsed())
+}
+
+/// Makes a new choice sequence from a random entry of the corpus.
+/// Any choices needed beyond the end of the result are drawn at random.
+fn mutate(corpus: &[Vec<u64>], rng: &mut RNG) -> Vec<u64> {
+    let mut choices = corpus[rng.gen_range(0..corpus.len())].clone();
+    if choices.is_empty() {
+        return choices;
+    }
+
+    let ix = rng.gen_range(0..choices.len());
+    match rng.gen_range(0..4) {
+        // replace one choice
+        0 => choices[ix] = rng.next_u64(),
+        // make one choice small, which most generators map to their minimum
+        1 => choices[ix] = rng.gen_range(0..16),
+        // keep only a prefix
+        2 => choices.truncate(ix),
+        // continue with the choices of another entry
+        _ => {
+            let other = &corpus[rng.gen_range(0..corpus.len())];
+            choices.truncate(ix);
+            choices.extend_from_slice(&other[ix.min(other.len())..]);
+        }
+    }
+
+    choices
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check_cfg, generators::i64};
+
+    #[test]
+    pub fn reaches_deep_branches() {
+        let test = |maat: &mut Maat| {
+            let x = maat.generate("x", i64(0, 64));
+            let y = maat.generate("y", i64(0, 64));
+            let z = maat.generate("z", i64(0, 64));
+            let progress = [x == 7, y == 13, z == 42]
+                .into_iter()
+                .take_while(|matched| *matched)
+                .count();
+
+            maat.observe(progress);
+            progress < 3
+        };
+
+        let cfg = Config::default().seed(1234).iterations(20_000);
+        assert!(check_cfg(test, &cfg).is_ok());
+
+        let failure = check_cfg(test, &cfg.search(Search::CoverageGuided)).unwrap_err();
+        let shrunk: Vec<_> = failure
+            .shrunk
+            .iter()
+            .map(|v| v.downcast_ref::<i64>())
+            .collect();
+        assert_eq!(shrunk, [Some(&7), Some(&13), Some(&42)]);
+    }
+
+    #[test]
+    pub fn maximizes_targets() {
+        let test = |maat: &mut Maat| {
+            let mut sum = 0;
+            for name in ["a", "b", "c", "d", "e"] {
+                sum += maat.generate(name, i64(0, 1000));
+            }
+
+            maat.target("sum", sum as f64);
+            sum <= 4800
+        };
+
+        let cfg = Config::default().seed(1234).iterations(20_000);
+        assert!(check_cfg(test, &cfg).is_ok());
+
+        let failure = check_cfg(test, &cfg.search(Search::Targeted)).unwrap_err();
+        let shrunk: i64 = failure
+            .shrunk
+            .iter()
+            .map(|v| v.downcast_ref::<i64>().unwrap())
+            .sum();
+        assert_eq!(shrunk, 4801);
+    }
+}
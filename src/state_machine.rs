@@ -0,0 +1,589 @@
+//! Stateful (model-based) testing.
+//!
+//! A [StateMachine] describes a system under test alongside a simpler
+//! model of its state. `maat` generates sequences of commands, runs them
+//! against the real system, and checks each output against the model.
+//!
+//! ```rust
+//! # use std::collections::VecDeque;
+//! # use maat::{generators::{derive, i64, u8}, property, state_machine::{self, StateMachine}, Generator};
+//! # #[derive(Clone)]
+//! # struct QueueModel;
+//! # #[derive(Clone, Debug)]
+//! # enum Command { Push(i64), Pop }
+//! # impl StateMachine for QueueModel {
+//! #     type State = VecDeque<i64>;
+//! #     type System = VecDeque<i64>;
+//! #     type Command = Command;
+//! #     type Output = Option<i64>;
+//! #     fn initial_state(&self) -> VecDeque<i64> { VecDeque::new() }
+//! #     fn new_system(&self) -> VecDeque<i64> { VecDeque::new() }
+//! #     fn command(&self, _state: &VecDeque<i64>) -> Box<dyn Generator<Command>> {
+//! #         Box::new(derive(|maat| match maat.generate("pop", u8(0, 2)) {
+//! #             0 => Command::Push(maat.generate("value", i64(0, 100))),
+//! #             _ => Command::Pop,
+//! #         }))
+//! #     }
+//! #     fn next_state(&self, state: &mut VecDeque<i64>, command: &Command) {
+//! #         match command {
+//! #             Command::Push(value) => state.push_back(*value),
+//! #             Command::Pop => { state.pop_front(); }
+//! #         }
+//! #     }
+//! #     fn execute(&self, system: &mut VecDeque<i64>, command: &Command) -> Option<i64> {
+//! #         match command {
+//! #             Command::Push(value) => { system.push_back(*value); None }
+//! #             Command::Pop => system.pop_front(),
+//! #         }
+//! #     }
+//! #     fn postcondition(&self, state: &VecDeque<i64>, command: &Command, output: &Option<i64>) -> bool {
+//! #         match command {
+//! #             Command::Push(_) => output.is_none(),
+//! #             Command::Pop => output.as_ref() == state.front(),
+//! #         }
+//! #     }
+//! # }
+//! property(|maat| state_machine::run(maat, &QueueModel, 50));
+//! ```
+//!
+//! When a failure is found, the command sequence is shrunk by deleting
+//! commands and by shrinking the individual commands. Commands whose
+//! precondition no longer holds after shrinking are skipped.
+//!
+//! A [ConcurrentStateMachine] can also be tested with [run_parallel],
+//! which runs several sequences of commands concurrently and checks
+//! that the outputs are linearizable: that is, that they can be
+//! explained by some sequential ordering of the commands.
+
+use std::{cell::RefCell, fmt::Debug, rc::Rc, sync::Barrier};
+
+use rand::Rng;
+
+use crate::{Generator, Maat, Mode, Recording, Shrinkable};
+
+pub trait StateMachine {
+    /// The model of the system’s state.
+    type State: Clone + Debug;
+    /// The real system under test.
+    type System;
+    type Command: Clone + Debug + 'static;
+    type Output: Debug;
+
+    fn initial_state(&self) -> Self::State;
+
+    fn new_system(&self) -> Self::System;
+
+    /// Returns a generator for commands that are appropriate for the given state.
+    fn command(&self, state: &Self::State) -> Box<dyn Generator<Self::Command>>;
+
+    /// Whether the command can be run in the given state.
+    fn precondition(&self, _state: &Self::State, _command: &Self::Command) -> bool {
+        true
+    }
+
+    /// Applies the command to the model.
+    fn next_state(&self, state: &mut Self::State, command: &Self::Command);
+
+    /// Applies the command to the real system.
+    fn execute(&self, system: &mut Self::System, command: &Self::Command) -> Self::Output;
+
+    /// Checks the output of the real system against the model,
+    /// which is in the state from before the command was applied.
+    fn postcondition(
+        &self,
+        state: &Self::State,
+        command: &Self::Command,
+        output: &Self::Output,
+    ) -> bool;
+}
+
+/// Generates a sequence of up to `max_length` commands, each of
+/// which satisfies its precondition when the commands are run in order.
+pub fn commands<M>(machine: M, max_length: usize) -> impl Generator<Vec<M::Command>>
+where
+    M: StateMachine + 'static,
+{
+    let initial_state = machine.initial_state();
+    commands_from(machine, initial_state, max_length)
+}
+
+/// Like [commands], but starting from the given model state.
+pub fn commands_from<M>(
+    machine: M,
+    initial_state: M::State,
+    max_length: usize,
+) -> impl Generator<Vec<M::Command>>
+where
+    M: StateMachine + 'static,
+{
+    struct G<M: StateMachine> {
+        machine: Rc<M>,
+        initial_state: M::State,
+        max_length: usize,
+    }
+
+    return G {
+        machine: Rc::new(machine),
+        initial_state,
+        max_length,
+    };
+
+    impl<M> Generator<Vec<M::Command>> for G<M>
+    where
+        M: StateMachine + 'static,
+    {
+        fn generate(&self, rng: &mut dyn rand::RngCore) -> Vec<M::Command> {
+            let length = rng.gen_range(0..=self.max_length);
+            let mut maat = Maat::new(Mode::Testing { rng });
+
+            generate_commands(&*self.machine, &self.initial_state, &mut maat, length)
+        }
+
+        fn generate_shrinkable(&self, rng: &mut dyn rand::RngCore) -> Shrinkable<Vec<M::Command>> {
+            let length = rng.gen_range(0..=self.max_length);
+            let mut recording = Vec::new();
+            let value = generate_commands(
+                &*self.machine,
+                &self.initial_state,
+                &mut Maat::new(Mode::Recording {
+                    rng,
+                    record: &mut recording,
+                }),
+                length,
+            );
+
+            let machine = self.machine.clone();
+            let initial_state = self.initial_state.clone();
+            let replay =
+                move |recording: &Recording| replay_commands(&*machine, &initial_state, recording);
+
+            let recording = RefCell::new(recording);
+            Shrinkable {
+                value,
+                shrink: Rc::new(move |_original_value, is_valid| {
+                    let mut ever_shrank = false;
+                    loop {
+                        let mut shrank_any = false;
+
+                        // first, try deleting commands
+                        let mut ix = recording.borrow().len();
+                        while ix > 0 {
+                            ix -= 1;
+                            let removed = recording.borrow_mut().remove(ix);
+                            if is_valid(replay(&recording.borrow())) {
+                                shrank_any = true;
+                            } else {
+                                recording.borrow_mut().insert(ix, removed);
+                            }
+                        }
+
+                        // then, try shrinking the remaining commands
+                        let recording = recording.borrow();
+                        for v in recording.iter() {
+                            while v.shrink(&mut || is_valid(replay(&recording))) {
+                                shrank_any = true;
+                            }
+                        }
+
+                        if !shrank_any {
+                            break;
+                        } else {
+                            ever_shrank = true;
+                        }
+                    }
+
+                    ever_shrank
+                }),
+            }
+        }
+    }
+}
+
+/// Generates commands until there are `length` of them, skipping any
+/// that don’t satisfy their preconditions.
+fn generate_commands<M: StateMachine>(
+    machine: &M,
+    initial_state: &M::State,
+    maat: &mut Maat,
+    length: usize,
+) -> Vec<M::Command> {
+    let mut state = initial_state.clone();
+    let mut result = Vec::with_capacity(length);
+    // give up eventually, if preconditions are hard to satisfy
+    for _ in 0..length * 10 {
+        if result.len() == length {
+            break;
+        }
+
+        let command = maat.generate("command", &*machine.command(&state));
+        if machine.precondition(&state, &command) {
+            machine.next_state(&mut state, &command);
+            result.push(command);
+        }
+    }
+
+    result
+}
+
+/// Rebuilds the command sequence from a (possibly shrunk) recording.
+fn replay_commands<M: StateMachine>(
+    machine: &M,
+    initial_state: &M::State,
+    recording: &Recording,
+) -> Vec<M::Command> {
+    let mut maat = Maat::new(Mode::Shrinking {
+        recording_ix: 0,
+        recording,
+    });
+
+    let mut state = initial_state.clone();
+    let mut result = Vec::with_capacity(recording.len());
+    for _ in 0..recording.len() {
+        let command = maat.generate("command", &*machine.command(&state));
+        if machine.precondition(&state, &command) {
+            machine.next_state(&mut state, &command);
+            result.push(command);
+        }
+    }
+
+    result
+}
+
+/// Generates a sequence of up to `max_length` commands and runs them
+/// against a new system, returning `false` if any postcondition fails.
+pub fn run<M>(maat: &mut Maat, machine: &M, max_length: usize) -> bool
+where
+    M: StateMachine + Clone + 'static,
+{
+    let commands = maat.generate("commands", commands(machine.clone(), max_length));
+    let mut state = machine.initial_state();
+    let mut system = machine.new_system();
+    for command in &commands {
+        let output = machine.execute(&mut system, command);
+        if !machine.postcondition(&state, command, &output) {
+            return false;
+        }
+
+        machine.next_state(&mut state, command);
+    }
+
+    true
+}
+
+/// A [StateMachine] whose system can be shared between threads.
+pub trait ConcurrentStateMachine: StateMachine {
+    /// Applies the command to the real system, which may be
+    /// concurrently running other commands on other threads.
+    fn execute_concurrent(&self, system: &Self::System, command: &Self::Command) -> Self::Output;
+}
+
+/// The number of times that the concurrent commands are run
+/// (each time against a new system), to make it more likely
+/// that a bad interleaving is observed.
+const PARALLEL_REPETITIONS: usize = 16;
+
+/// Generates a sequential prefix of up to `max_length` commands, followed
+/// by a suffix of up to `max_length` commands for each of the `threads`.
+///
+/// The prefix is run first, then the suffixes are run concurrently. Returns
+/// `false` if there is no ordering of the suffix commands which would produce
+/// the observed outputs when run sequentially against the model.
+///
+/// The number of orderings grows very quickly, so `threads`
+/// and `max_length` should be kept small.
+pub fn run_parallel<M>(maat: &mut Maat, machine: &M, threads: usize, max_length: usize) -> bool
+where
+    M: ConcurrentStateMachine + Clone + Sync + 'static,
+    M::System: Sync,
+    M::Command: Sync,
+    M::Output: Send,
+{
+    let prefix = maat.generate("prefix", commands(machine.clone(), max_length));
+    let mut state = machine.initial_state();
+    for command in &prefix {
+        machine.next_state(&mut state, command);
+    }
+
+    let suffixes: Vec<Vec<M::Command>> = (0..threads)
+        .map(|_| {
+            maat.generate(
+                "suffix",
+                commands_from(machine.clone(), state.clone(), max_length),
+            )
+        })
+        .collect();
+
+    // after shrinking, the suffixes might no longer be valid
+    // after the prefix; such cases are not counterexamples
+    let suffixes: Vec<&[M::Command]> = suffixes.iter().map(Vec::as_slice).collect();
+    if !preconditions_hold(machine, &state, &suffixes) {
+        return true;
+    }
+
+    for _ in 0..PARALLEL_REPETITIONS {
+        let mut system = machine.new_system();
+        let mut state = machine.initial_state();
+        for command in &prefix {
+            let output = machine.execute(&mut system, command);
+            if !machine.postcondition(&state, command, &output) {
+                return false;
+            }
+
+            machine.next_state(&mut state, command);
+        }
+
+        let barrier = Barrier::new(threads);
+        let outputs: Vec<Vec<M::Output>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = suffixes
+                .iter()
+                .map(|suffix| {
+                    let (system, barrier) = (&system, &barrier);
+                    scope.spawn(move || {
+                        barrier.wait();
+                        suffix
+                            .iter()
+                            .map(|command| machine.execute_concurrent(system, command))
+                            .collect()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let history: Vec<Vec<(&M::Command, &M::Output)>> = suffixes
+            .iter()
+            .zip(&outputs)
+            .map(|(suffix, outputs)| suffix.iter().zip(outputs).collect())
+            .collect();
+
+        if !linearizable(machine, &state, &history) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Checks that every ordering of the commands satisfies their preconditions.
+fn preconditions_hold<M: StateMachine>(
+    machine: &M,
+    state: &M::State,
+    suffixes: &[&[M::Command]],
+) -> bool {
+    suffixes
+        .iter()
+        .enumerate()
+        .all(|(ix, suffix)| match suffix {
+            [] => true,
+            [command, rest @ ..] => {
+                if !machine.precondition(state, command) {
+                    return false;
+                }
+
+                let mut next_state = state.clone();
+                machine.next_state(&mut next_state, command);
+                let mut remaining = suffixes.to_vec();
+                remaining[ix] = rest;
+                preconditions_hold(machine, &next_state, &remaining)
+            }
+        })
+}
+
+/// Searches for an ordering of the commands (preserving the order
+/// within each thread) that is consistent with the model.
+fn linearizable<M: StateMachine>(
+    machine: &M,
+    state: &M::State,
+    history: &[Vec<(&M::Command, &M::Output)>],
+) -> bool {
+    if history.iter().all(Vec::is_empty) {
+        return true;
+    }
+
+    history
+        .iter()
+        .enumerate()
+        .any(|(ix, thread)| match thread.as_slice() {
+            [] => false,
+            [(command, output), ..] => {
+                if !machine.postcondition(state, command, output) {
+                    return false;
+                }
+
+                let mut next_state = state.clone();
+                machine.next_state(&mut next_state, command);
+                let mut remaining = history.to_vec();
+                remaining[ix].remove(0);
+                linearizable(machine, &next_state, &remaining)
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check, generators::i64};
+
+    /// A stack that loses its oldest element once it has more than three.
+    struct LossyStack(Vec<i64>);
+
+    #[derive(Clone)]
+    struct StackModel;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Command {
+        Push(i64),
+        Pop,
+    }
+
+    impl StateMachine for StackModel {
+        type State = Vec<i64>;
+        type System = LossyStack;
+        type Command = Command;
+        type Output = Option<i64>;
+
+        fn initial_state(&self) -> Vec<i64> {
+            Vec::new()
+        }
+
+        fn new_system(&self) -> LossyStack {
+            LossyStack(Vec::new())
+        }
+
+        fn command(&self, state: &Vec<i64>) -> Box<dyn Generator<Command>> {
+            let pushes_only = state.is_empty();
+            Box::new(crate::generators::derive(move |maat| {
+                let pop = maat.generate("pop", crate::generators::u8(0, 2)) == 1;
+                let value = maat.generate("value", i64(0, 100));
+                if pop && !pushes_only {
+                    Command::Pop
+                } else {
+                    Command::Push(value)
+                }
+            }))
+        }
+
+        fn precondition(&self, state: &Vec<i64>, command: &Command) -> bool {
+            *command != Command::Pop || !state.is_empty()
+        }
+
+        fn next_state(&self, state: &mut Vec<i64>, command: &Command) {
+            match command {
+                Command::Push(value) => state.push(*value),
+                Command::Pop => {
+                    state.pop();
+                }
+            }
+        }
+
+        fn execute(&self, system: &mut LossyStack, command: &Command) -> Option<i64> {
+            match command {
+                Command::Push(value) => {
+                    system.0.push(*value);
+                    if system.0.len() > 3 {
+                        system.0.remove(0);
+                    }
+
+                    None
+                }
+                Command::Pop => system.0.pop(),
+            }
+        }
+
+        fn postcondition(&self, state: &Vec<i64>, command: &Command, output: &Option<i64>) -> bool {
+            match command {
+                Command::Push(_) => output.is_none(),
+                Command::Pop => *output == state.last().copied(),
+            }
+        }
+    }
+
+    #[test]
+    pub fn finds_minimal_command_sequence() {
+        let failure = check(|maat| run(maat, &StackModel, 20)).unwrap_err();
+        let commands = failure.shrunk[0].downcast_ref::<Vec<Command>>().unwrap();
+
+        // the shortest failure is four pushes, then popping all of them
+        assert_eq!(commands.len(), 8);
+        assert_eq!(commands[0], Command::Push(0));
+    }
+
+    /// A counter whose increments are not atomic.
+    struct RacyCounter(std::sync::atomic::AtomicI64);
+
+    #[derive(Clone)]
+    struct CounterModel;
+
+    #[derive(Clone, Debug)]
+    enum CounterCommand {
+        Increment,
+        Get,
+    }
+
+    impl StateMachine for CounterModel {
+        type State = i64;
+        type System = RacyCounter;
+        type Command = CounterCommand;
+        type Output = i64;
+
+        fn initial_state(&self) -> i64 {
+            0
+        }
+
+        fn new_system(&self) -> RacyCounter {
+            RacyCounter(0.into())
+        }
+
+        fn command(&self, _state: &i64) -> Box<dyn Generator<CounterCommand>> {
+            Box::new(crate::generators::derive(|maat| {
+                match maat.generate("increment", crate::generators::u8(0, 2)) {
+                    0 => CounterCommand::Get,
+                    _ => CounterCommand::Increment,
+                }
+            }))
+        }
+
+        fn next_state(&self, state: &mut i64, command: &CounterCommand) {
+            if let CounterCommand::Increment = command {
+                *state += 1;
+            }
+        }
+
+        fn execute(&self, system: &mut RacyCounter, command: &CounterCommand) -> i64 {
+            self.execute_concurrent(system, command)
+        }
+
+        fn postcondition(&self, state: &i64, _command: &CounterCommand, output: &i64) -> bool {
+            output == state
+        }
+    }
+
+    impl ConcurrentStateMachine for CounterModel {
+        fn execute_concurrent(&self, system: &RacyCounter, command: &CounterCommand) -> i64 {
+            use std::sync::atomic::Ordering::SeqCst;
+            let value = system.0.load(SeqCst);
+            if let CounterCommand::Increment = command {
+                std::thread::sleep(std::time::Duration::from_micros(100));
+                system.0.store(value + 1, SeqCst);
+            }
+
+            value
+        }
+    }
+
+    #[test]
+    pub fn finds_non_linearizable_history() {
+        let failure = check(|maat| run_parallel(maat, &CounterModel, 2, 3)).unwrap_err();
+        let suffixes: Vec<_> = failure.shrunk[1..]
+            .iter()
+            .map(|v| v.downcast_ref::<Vec<CounterCommand>>().unwrap().len())
+            .collect();
+
+        // the race needs at least one increment on each thread
+        assert!(suffixes.iter().all(|&length| length >= 1));
+    }
+}
@@ -0,0 +1,62 @@
+//! Checks that the regression tests generated for a failure compile,
+//! by comparing the generated source with the tests written out below
+//! (which rustfmt must leave alone).
+
+use maat::{
+    check,
+    generators::{derive, i64},
+    property, Maat,
+};
+
+fn is_small(maat: &mut Maat) -> bool {
+    maat.generate_serializable("x", i64(0, 100)) < 10
+}
+
+fn small_numbers() {
+    property(is_small);
+}
+
+#[should_panic(expected = "x: i64 = 10")]
+#[rustfmt::skip]
+#[test]
+fn small_numbers_regression() {
+    let replay = maat::Replay::from_json(r#"[{"name":"x","type_name":"i64","value":10}]"#).unwrap();
+    maat::with_replay(&replay, small_numbers);
+}
+
+#[derive(Clone, Debug)]
+struct Point {
+    x: i64,
+}
+
+fn is_near(maat: &mut Maat) -> bool {
+    let point = derive(|maat| Point {
+        x: maat.generate("x", i64(0, 100)),
+    });
+    maat.generate("point", point).x < 10
+}
+
+fn near_points() {
+    property(is_near);
+}
+
+#[should_panic(expected = "x: 10")]
+#[rustfmt::skip]
+#[test]
+fn near_points_regression() {
+    let replay = maat::Replay::new()
+        .value::<Point>("point", Point { x: 10 });
+    maat::with_replay(&replay, near_points);
+}
+
+#[test]
+fn generates_the_regression_tests_above() {
+    let source = include_str!("regression.rs");
+    for (test, name) in [
+        (is_small as fn(&mut Maat) -> bool, "small_numbers"),
+        (is_near, "near_points"),
+    ] {
+        let generated = check(test).unwrap_err().regression_test(name);
+        assert!(source.contains(&generated), "{generated}");
+    }
+}